@@ -0,0 +1,152 @@
+// Compares the `solve` word map with `InternedIndex`: build time and the
+// memory each one keeps. Run with
+//
+//   cargo bench -p simple-parser --bench interning [-- <file>...]
+//
+// Without files a synthetic text is generated whose word frequencies roughly
+// follow Zipf's law.
+use simple_parser::{read_file, solve_with, InternedIndex, Normalizer};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// Tracks the bytes currently allocated and the highest value since the last
+// reset.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+struct Measurement {
+    time: Duration,
+    retained: usize,
+    peak: usize,
+}
+
+// Best time of a few runs; memory of the last one, as kept by the result and
+// at its highest during the build.
+fn measure<T>(build: impl Fn() -> T) -> Measurement {
+    let mut time = Duration::MAX;
+    let mut retained = 0;
+    let mut peak = 0;
+    for _ in 0..3 {
+        let before = ALLOCATED.load(Ordering::Relaxed);
+        PEAK.store(before, Ordering::Relaxed);
+        let start = Instant::now();
+        let result = build();
+        time = time.min(start.elapsed());
+        retained = ALLOCATED.load(Ordering::Relaxed) - before;
+        peak = PEAK.load(Ordering::Relaxed) - before;
+        drop(result);
+    }
+    Measurement {
+        time,
+        retained,
+        peak,
+    }
+}
+
+// Words are picked with a log-uniform rank, which gives the long tail of rare
+// words real text has. A fixed linear congruential generator keeps the text
+// the same between runs.
+fn synthetic_text(lines: usize, words_per_line: usize, vocabulary: u64) -> String {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let mut text = String::new();
+    for _ in 0..lines {
+        for i in 0..words_per_line {
+            let mut rank = (vocabulary as f64).powf(next()) as u64;
+            if i > 0 {
+                text.push(' ');
+            }
+            loop {
+                text.push((b'a' + (rank % 26) as u8) as char);
+                rank /= 26;
+                if rank == 0 {
+                    break;
+                }
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn report(name: &str, content: &str) {
+    let normalizer = Normalizer::default();
+    let solved = measure(|| solve_with(content, &normalizer));
+    let interned = measure(|| InternedIndex::from_text(content, &normalizer));
+    let terms = InternedIndex::from_text(content, &normalizer).len();
+
+    println!(
+        "{} ({} bytes, {} lines, {} terms)",
+        name,
+        content.len(),
+        content.lines().count(),
+        terms
+    );
+    println!(
+        "{:<28} {:>12} {:>14} {:>14}",
+        "", "build", "retained", "peak"
+    );
+    for (label, measurement) in [
+        ("BTreeMap<String, BTreeSet>", &solved),
+        ("InternedIndex", &interned),
+    ] {
+        println!(
+            "{:<28} {:>10.1}ms {:>12.1}MiB {:>12.1}MiB",
+            label,
+            measurement.time.as_secs_f64() * 1000.0,
+            measurement.retained as f64 / (1 << 20) as f64,
+            measurement.peak as f64 / (1 << 20) as f64
+        );
+    }
+    println!(
+        "{:<28} {:>11.2}x {:>13.2}x {:>13.2}x\n",
+        "improvement",
+        solved.time.as_secs_f64() / interned.time.as_secs_f64(),
+        solved.retained as f64 / interned.retained.max(1) as f64,
+        solved.peak as f64 / interned.peak.max(1) as f64
+    );
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`; everything else is a file to index.
+    let files: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    if files.is_empty() {
+        report("synthetic", &synthetic_text(200_000, 12, 100_000));
+    }
+    for file in files {
+        match read_file(&file) {
+            Ok(content) => report(&file, &content),
+            Err(e) => eprintln!("Error reading '{}': {}", file, e),
+        }
+    }
+}
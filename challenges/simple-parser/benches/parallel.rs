@@ -0,0 +1,137 @@
+// Compares `solve_with` with `solve_parallel_with`, which merges the partial
+// maps of neighbouring chunks pairwise on several threads, and with merging
+// the same partial maps one after another on the calling thread, as
+// `solve_parallel_with` used to. Run with
+//
+//   cargo bench -p simple-parser --bench parallel [-- <file>...]
+//
+// Without files a synthetic text is generated like in the interning bench.
+use simple_parser::{read_file, solve_parallel_with, solve_with, Normalizer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREADS: [usize; 4] = [2, 4, 8, 16];
+
+// Best time of a few runs.
+fn measure<T>(build: impl Fn() -> T) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            drop(build());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn synthetic_text(lines: usize, words_per_line: usize, vocabulary: u64) -> String {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let mut text = String::new();
+    for _ in 0..lines {
+        for i in 0..words_per_line {
+            let mut rank = (vocabulary as f64).powf(next()) as u64;
+            if i > 0 {
+                text.push(' ');
+            }
+            loop {
+                text.push((b'a' + (rank % 26) as u8) as char);
+                rank /= 26;
+                if rank == 0 {
+                    break;
+                }
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+// The previous `solve_parallel_with`: chunks are indexed in parallel and the
+// partial maps merged in order on the calling thread.
+fn solve_serial_merge(
+    content: &str,
+    normalizer: &Normalizer,
+    threads: usize,
+) -> BTreeMap<String, BTreeSet<usize>> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let chunks: Vec<String> = lines
+        .chunks(lines.len().div_ceil(threads).max(1))
+        .map(|chunk| chunk.concat())
+        .collect();
+    let partials: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .iter()
+            .map(|chunk| {
+                scope.spawn(move || (solve_with(chunk, normalizer), chunk.lines().count()))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    let mut word_map: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+    let mut offset = 0;
+    for (partial, lines) in partials {
+        for (word, chunk_lines) in partial {
+            word_map
+                .entry(word)
+                .or_default()
+                .extend(chunk_lines.into_iter().map(|line| line + offset));
+        }
+        offset += lines;
+    }
+    word_map
+}
+
+fn report(name: &str, content: &str) {
+    let normalizer = Normalizer::default();
+    println!(
+        "{} ({} bytes, {} lines), {} available thread(s)",
+        name,
+        content.len(),
+        content.lines().count(),
+        thread::available_parallelism().map_or(1, |threads| threads.get())
+    );
+    let single = measure(|| solve_with(content, &normalizer));
+    println!(
+        "{:<8} {:>14} {:>14}",
+        "threads", "serial merge", "tree merge"
+    );
+    println!("{:<8} {:>12.1}ms", 1, single.as_secs_f64() * 1000.0);
+    for threads in THREADS {
+        let serial = measure(|| solve_serial_merge(content, &normalizer, threads));
+        let tree = measure(|| solve_parallel_with(content, &normalizer, threads));
+        println!(
+            "{:<8} {:>12.1}ms {:>12.1}ms",
+            threads,
+            serial.as_secs_f64() * 1000.0,
+            tree.as_secs_f64() * 1000.0
+        );
+    }
+    println!();
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`; everything else is a file to index.
+    let files: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    if files.is_empty() {
+        report("synthetic", &synthetic_text(200_000, 12, 100_000));
+    }
+    for file in files {
+        match read_file(&file) {
+            Ok(content) => report(&file, &content),
+            Err(e) => eprintln!("Error reading '{}': {}", file, e),
+        }
+    }
+}
@@ -0,0 +1,487 @@
+use crate::Field;
+
+// Strict and reserved keywords of Rust 2021.
+#[rustfmt::skip]
+pub const RUST_KEYWORDS: &[&str] = &[
+    "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+// C and C++ keywords, the built-in types and the preprocessor directives.
+#[rustfmt::skip]
+const C_KEYWORDS: &[&str] = &[
+    "NULL", "alignas", "alignof", "asm", "auto", "bool", "break", "case", "catch", "char", "class",
+    "const", "const_cast", "constexpr", "continue", "decltype", "default", "define", "delete",
+    "do", "double", "dynamic_cast", "elif", "else", "endif", "enum", "explicit", "export",
+    "extern", "false", "float", "for", "friend", "goto", "if", "ifdef", "ifndef", "include",
+    "inline", "int", "long", "mutable", "namespace", "new", "noexcept", "nullptr", "operator",
+    "pragma", "private", "protected", "public", "register", "reinterpret_cast", "restrict",
+    "return", "short", "signed", "sizeof", "static", "static_assert", "static_cast", "struct",
+    "switch", "template", "this", "throw", "true", "try", "typedef", "typeid", "typename", "undef",
+    "union", "unsigned", "using", "virtual", "void", "volatile", "wchar_t", "while",
+];
+
+#[rustfmt::skip]
+const JAVA_KEYWORDS: &[&str] = &[
+    "abstract", "assert", "boolean", "break", "byte", "case", "catch", "char", "class", "const",
+    "continue", "default", "do", "double", "else", "enum", "extends", "false", "final", "finally",
+    "float", "for", "goto", "if", "implements", "import", "instanceof", "int", "interface", "long",
+    "native", "new", "null", "package", "permits", "private", "protected", "public", "record",
+    "return", "sealed", "short", "static", "strictfp", "super", "switch", "synchronized", "this",
+    "throw", "throws", "transient", "true", "try", "var", "void", "volatile", "while", "yield",
+];
+
+// JavaScript keywords and the TypeScript ones on top.
+#[rustfmt::skip]
+const JAVASCRIPT_KEYWORDS: &[&str] = &[
+    "abstract", "any", "as", "async", "await", "boolean", "break", "case", "catch", "class",
+    "const", "continue", "debugger", "declare", "default", "delete", "do", "else", "enum",
+    "export", "extends", "false", "finally", "for", "from", "function", "if", "implements",
+    "import", "in", "instanceof", "interface", "keyof", "let", "module", "namespace", "never",
+    "new", "null", "number", "of", "private", "protected", "public", "readonly", "return",
+    "static", "string", "super", "switch", "this", "throw", "true", "try", "type", "typeof",
+    "undefined", "unknown", "var", "void", "while", "with", "yield",
+];
+
+// Go keywords and predeclared identifiers.
+#[rustfmt::skip]
+const GO_KEYWORDS: &[&str] = &[
+    "bool", "break", "byte", "case", "chan", "complex128", "complex64", "const", "continue",
+    "default", "defer", "else", "error", "fallthrough", "false", "float32", "float64", "for",
+    "func", "go", "goto", "if", "import", "int", "int16", "int32", "int64", "int8", "interface",
+    "iota", "map", "nil", "package", "range", "return", "rune", "select", "string", "struct",
+    "switch", "true", "type", "uint", "uint16", "uint32", "uint64", "uint8", "uintptr", "var",
+];
+
+#[rustfmt::skip]
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "case", "class",
+    "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if",
+    "import", "in", "is", "lambda", "match", "nonlocal", "not", "or", "pass", "raise", "return",
+    "try", "while", "with", "yield",
+];
+
+// Reserved words of the POSIX shell and Bash, and the builtins that declare
+// or leave.
+#[rustfmt::skip]
+const SHELL_KEYWORDS: &[&str] = &[
+    "case", "coproc", "declare", "do", "done", "elif", "else", "esac", "exit", "export", "fi",
+    "for", "function", "if", "in", "local", "readonly", "return", "select", "then", "time",
+    "until", "while",
+];
+
+const TOML_KEYWORDS: &[&str] = &["false", "inf", "nan", "true"];
+
+// The languages the code format knows the keywords, comments and string
+// literals of. The C family (`//` and `/* */` comments) and the `#` family
+// differ in how they are cut into identifiers, comments and strings; what
+// a language has beyond that, such as C++ raw strings, heredocs in shell or
+// `${...}` inside JavaScript templates, is read as plain code or string text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    Rust,
+    // C and C++.
+    C,
+    Java,
+    // JavaScript and TypeScript.
+    JavaScript,
+    Go,
+    Python,
+    // POSIX shell and Bash.
+    Shell,
+    Toml,
+}
+
+impl Language {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rust" | "rs" => Some(Language::Rust),
+            "c" | "cpp" | "c++" => Some(Language::C),
+            "java" => Some(Language::Java),
+            "javascript" | "js" | "typescript" | "ts" => Some(Language::JavaScript),
+            "go" => Some(Language::Go),
+            "python" | "py" => Some(Language::Python),
+            "shell" | "sh" | "bash" => Some(Language::Shell),
+            "toml" => Some(Language::Toml),
+            _ => None,
+        }
+    }
+
+    // The language of a file by its extension.
+    pub fn from_extension(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        match extension {
+            "rs" => Some(Language::Rust),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hh" | "hpp" => Some(Language::C),
+            "java" => Some(Language::Java),
+            "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" => Some(Language::JavaScript),
+            "go" => Some(Language::Go),
+            "py" | "pyi" => Some(Language::Python),
+            "sh" | "bash" => Some(Language::Shell),
+            "toml" => Some(Language::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => RUST_KEYWORDS,
+            Language::C => C_KEYWORDS,
+            Language::Java => JAVA_KEYWORDS,
+            Language::JavaScript => JAVASCRIPT_KEYWORDS,
+            Language::Go => GO_KEYWORDS,
+            Language::Python => PYTHON_KEYWORDS,
+            Language::Shell => SHELL_KEYWORDS,
+            Language::Toml => TOML_KEYWORDS,
+        }
+    }
+
+    fn hash_comments(self) -> bool {
+        matches!(self, Language::Python | Language::Shell | Language::Toml)
+    }
+
+    // The string literal starting at `rest`, if any: the length of its prefix
+    // and opening quotes, and how it ends. Rust's char literals are not
+    // strings; in the other C family languages they are read as strings.
+    fn string_start(self, rest: &str) -> Option<(usize, StrEnd)> {
+        let quoted = |prefix: usize, escapes: bool| {
+            let quote = rest.as_bytes()[prefix];
+            Some((prefix + 1, StrEnd::new(quote, 1, 0, escapes)))
+        };
+        let tripled = |prefix: usize, escapes: bool| {
+            let quote = rest.as_bytes()[prefix];
+            let triple =
+                rest[prefix..].bytes().take(3).all(|b| b == quote) && rest.len() >= prefix + 3;
+            match triple {
+                true => Some((prefix + 3, StrEnd::new(quote, 3, 0, escapes))),
+                false => quoted(prefix, escapes),
+            }
+        };
+        match (self, rest.as_bytes()[0]) {
+            (Language::Rust, _) => rust_string_start(rest),
+            (Language::C | Language::Java | Language::JavaScript, b'"' | b'\'') => quoted(0, true),
+            (Language::JavaScript, b'`') => quoted(0, true),
+            (Language::Go, b'"' | b'\'') => quoted(0, true),
+            (Language::Go, b'`') => quoted(0, false),
+            (Language::Shell, b'"') => quoted(0, true),
+            (Language::Shell, b'\'') => quoted(0, false),
+            (Language::Toml, b'"') => tripled(0, true),
+            (Language::Toml, b'\'') => tripled(0, false),
+            (Language::Python, _) => {
+                // `r"..."`, `b'...'`, `f"""..."""` and the like.
+                let prefix = rest
+                    .find(|c: char| !"rRbBfFuU".contains(c))
+                    .unwrap_or(rest.len());
+                match rest.as_bytes().get(prefix) {
+                    Some(b'"' | b'\'') if prefix <= 2 => tripled(prefix, true),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+// Where a line of source code starts: in code, or inside a block comment or a
+// string literal left open by an earlier line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum CodeState {
+    #[default]
+    Code,
+    // Rust block comments nest, the depth is 1 in other languages.
+    BlockComment(usize),
+    Str(StrEnd),
+}
+
+// How a string literal ends: `quotes` times `quote` (three for Python's and
+// TOML's multi-line strings), followed by `hashes` `#` for Rust raw strings.
+// A backslash escapes the next character if `escapes` is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct StrEnd {
+    quote: u8,
+    quotes: usize,
+    hashes: usize,
+    escapes: bool,
+}
+
+impl StrEnd {
+    fn new(quote: u8, quotes: usize, hashes: usize, escapes: bool) -> Self {
+        StrEnd {
+            quote,
+            quotes,
+            hashes,
+            escapes,
+        }
+    }
+
+    fn len(self) -> usize {
+        self.quotes + self.hashes
+    }
+
+    // Where the string that `text` is in closes.
+    fn find(self, text: &str) -> Option<usize> {
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            if self.escapes && bytes[i] == b'\\' {
+                i += 2;
+                continue;
+            }
+            let closes = bytes[i..].len() >= self.len()
+                && bytes[i..i + self.quotes].iter().all(|&b| b == self.quote)
+                && bytes[i + self.quotes..i + self.len()]
+                    .iter()
+                    .all(|&b| b == b'#');
+            if closes {
+                return Some(i);
+            }
+            i += 1;
+        }
+        None
+    }
+}
+
+// The words of an identifier at snake_case and camelCase boundaries:
+// `parse_query` -> `parse`, `query`; `HTTPServerError` -> `HTTP`, `Server`,
+// `Error`; `utf8Decode` -> `utf8`, `Decode`. Byte ranges in `identifier`.
+pub fn identifier_parts(identifier: &str) -> Vec<(usize, usize)> {
+    let mut parts = Vec::new();
+    let mut offset = 0;
+    for segment in identifier.split('_') {
+        let chars: Vec<(usize, char)> = segment.char_indices().collect();
+        let mut start = 0;
+        for (i, &(index, c)) in chars.iter().enumerate().skip(1) {
+            let previous = chars[i - 1].1;
+            let next_is_lower = chars
+                .get(i + 1)
+                .is_some_and(|&(_, next)| next.is_lowercase());
+            let boundary = c.is_uppercase()
+                && (previous.is_lowercase()
+                    || previous.is_ascii_digit()
+                    || (previous.is_uppercase() && next_is_lower));
+            if boundary {
+                parts.push((offset + start, offset + index));
+                start = index;
+            }
+        }
+        if start < segment.len() {
+            parts.push((offset + start, offset + segment.len()));
+        }
+        offset += segment.len() + 1;
+    }
+    parts
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Cuts a line of source code into fields, in line order: every identifier
+// that is not a keyword of `language`, followed by its parts if it has more
+// than one, and optionally the text of comments and string literals as
+// fields named `comment` and `string` that are not indexed as plain words.
+pub(crate) fn split_code(
+    state: &mut CodeState,
+    language: Language,
+    comments: bool,
+    strings: bool,
+    line: &str,
+    fields: &mut Vec<Field>,
+) {
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        match *state {
+            CodeState::BlockComment(depth) => {
+                let nested = language == Language::Rust;
+                let (end, depth) = block_comment_end(rest, depth, nested);
+                push_text(
+                    fields,
+                    i,
+                    i + end.unwrap_or(rest.len()),
+                    "comment",
+                    comments,
+                );
+                match end {
+                    Some(end) => {
+                        *state = CodeState::Code;
+                        i += end + 2;
+                    }
+                    None => {
+                        *state = CodeState::BlockComment(depth);
+                        i = line.len();
+                    }
+                }
+            }
+            CodeState::Str(end) => {
+                let close = end.find(rest);
+                push_text(
+                    fields,
+                    i,
+                    i + close.unwrap_or(rest.len()),
+                    "string",
+                    strings,
+                );
+                match close {
+                    Some(close) => {
+                        *state = CodeState::Code;
+                        i += close + end.len();
+                    }
+                    None => i = line.len(),
+                }
+            }
+            CodeState::Code => {
+                let c = rest.chars().next().unwrap();
+                if let Some(comment) = line_comment(language, line, i) {
+                    // Doc comments start with `///`, `//!` or `#!`.
+                    let start = line.len() - comment.trim_start_matches(['/', '!']).len();
+                    push_text(
+                        fields,
+                        start,
+                        line.trim_end().len().max(start),
+                        "comment",
+                        comments,
+                    );
+                    i = line.len();
+                } else if !language.hash_comments() && rest.starts_with("/*") {
+                    *state = CodeState::BlockComment(1);
+                    i += 2;
+                } else if let Some((prefix, end)) = language.string_start(rest) {
+                    *state = CodeState::Str(end);
+                    i += prefix;
+                } else if language == Language::Rust && c == '\'' {
+                    i += char_literal_len(rest);
+                } else if language == Language::Rust && rest.starts_with("b'") {
+                    i += 1 + char_literal_len(&rest[1..]);
+                } else if c.is_alphabetic() || c == '_' {
+                    let len = rest
+                        .find(|c: char| !is_identifier_char(c))
+                        .unwrap_or(rest.len());
+                    // The name of a lifetime is not an identifier.
+                    if !line[..i].ends_with('\'') {
+                        push_identifier(fields, language, line, i, i + len);
+                    }
+                    i += len;
+                } else if c.is_ascii_digit() {
+                    i += rest
+                        .find(|c: char| !is_identifier_char(c))
+                        .unwrap_or(rest.len());
+                } else {
+                    i += c.len_utf8();
+                }
+            }
+        }
+    }
+}
+
+fn push_identifier(
+    fields: &mut Vec<Field>,
+    language: Language,
+    line: &str,
+    start: usize,
+    end: usize,
+) {
+    let identifier = &line[start..end];
+    let keywords = language.keywords();
+    if keywords.contains(&identifier) || identifier.trim_matches('_').is_empty() {
+        return;
+    }
+    fields.push(Field::text(start, end));
+    let parts = identifier_parts(identifier);
+    if parts.len() > 1 {
+        for (part_start, part_end) in parts {
+            if !keywords.contains(&&identifier[part_start..part_end]) {
+                fields.push(Field::text(start + part_start, start + part_end));
+            }
+        }
+    }
+}
+
+// Comment and string text, indexed only as `name:word`.
+fn push_text(fields: &mut Vec<Field>, start: usize, end: usize, name: &str, wanted: bool) {
+    if wanted && start < end {
+        fields.push(Field {
+            start,
+            end,
+            name: Some(name.to_string()),
+            plain: false,
+        });
+    }
+}
+
+// The text after the marker if a line comment starts at byte `i`. In shell
+// `#` starts a comment only at the start of a word, so `$#` and `${#x}` do
+// not.
+fn line_comment(language: Language, line: &str, i: usize) -> Option<&str> {
+    if !language.hash_comments() {
+        return line[i..].strip_prefix("//");
+    }
+    let comment = line[i..].strip_prefix('#')?;
+    let word_start = line[..i]
+        .chars()
+        .next_back()
+        .is_none_or(|c| c.is_whitespace() || ";|&(".contains(c));
+    (language != Language::Shell || word_start).then_some(comment)
+}
+
+// Where the comment that `text` is in closes (at the `*/`), and the nesting
+// depth at the end of `text` if it does not.
+fn block_comment_end(text: &str, mut depth: usize, nested: bool) -> (Option<usize>, usize) {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        match &bytes[i..i + 2] {
+            b"*/" => {
+                depth -= 1;
+                if depth == 0 {
+                    return (Some(i), 0);
+                }
+                i += 2;
+            }
+            b"/*" if nested => {
+                depth += 1;
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    (None, depth)
+}
+
+// `"`, `b"`, `r"`, `br#"` and the like: the length of the prefix including
+// the quote, and how the string ends.
+fn rust_string_start(text: &str) -> Option<(usize, StrEnd)> {
+    let after_b = text.strip_prefix('b').unwrap_or(text);
+    if after_b.starts_with('"') {
+        return Some((
+            text.len() - after_b.len() + 1,
+            StrEnd::new(b'"', 1, 0, true),
+        ));
+    }
+    let after_r = after_b.strip_prefix('r')?;
+    let hashes = after_r.len() - after_r.trim_start_matches('#').len();
+    after_r[hashes..].starts_with('"').then(|| {
+        let prefix = text.len() - after_r.len() + hashes + 1;
+        (prefix, StrEnd::new(b'"', 1, hashes, false))
+    })
+}
+
+// `'x'` and `'\n'` are skipped whole; the quote of a lifetime like `'a` alone.
+fn char_literal_len(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '\\')) => text
+            .get(3..)
+            .and_then(|rest| rest.find('\''))
+            .map_or(1, |close| close + 4),
+        Some((_, c)) => match chars.next() {
+            Some((close, '\'')) => close + 1,
+            _ => 1 + c.len_utf8() * usize::from(!is_identifier_char(c)),
+        },
+        None => 1,
+    }
+}
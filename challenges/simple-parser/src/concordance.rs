@@ -0,0 +1,101 @@
+use std::fmt;
+
+use crate::Index;
+
+const HIGHLIGHT: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+// One keyword-in-context line: up to `width` characters of the line on each
+// side of the occurrence. `column` counts characters from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KwicLine {
+    pub line: usize,
+    pub column: usize,
+    pub left: String,
+    pub keyword: String,
+    pub right: String,
+    pub width: usize,
+}
+
+// Keyword column aligned: the left context is right-aligned and the right
+// context left-aligned in `width` characters.
+impl fmt::Display for KwicLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>6}:{:<4} {:>width$}  {}  {}",
+            self.line,
+            self.column,
+            self.left,
+            self.keyword,
+            self.right,
+            width = self.width
+        )
+    }
+}
+
+// The concordance of `term` in a text indexed by `index`. `lines` are the
+// lines of that text, `lines[0]` being line 1.
+pub fn concordance<S: AsRef<str>>(
+    index: &Index,
+    lines: &[S],
+    term: &str,
+    width: usize,
+) -> Vec<KwicLine> {
+    index
+        .occurrences(term)
+        .into_iter()
+        .filter_map(|occurrence| {
+            let text = lines.get(occurrence.line - 1)?.as_ref();
+            let (before, keyword, after) = split_at_span(text, occurrence.start, occurrence.end)?;
+            let left: Vec<char> = flatten_whitespace(before).chars().collect();
+            Some(KwicLine {
+                line: occurrence.line,
+                column: before.chars().count() + 1,
+                left: left[left.len().saturating_sub(width)..].iter().collect(),
+                keyword: keyword.to_string(),
+                right: flatten_whitespace(after).chars().take(width).collect(),
+                width,
+            })
+        })
+        .collect()
+}
+
+// `line` with every byte range in `spans` wrapped in ANSI bold red.
+pub fn highlight(line: &str, spans: &[(usize, usize)]) -> String {
+    let mut spans = spans.to_vec();
+    spans.sort();
+    spans.dedup();
+    let mut out = String::with_capacity(line.len() + spans.len() * (HIGHLIGHT.len() + RESET.len()));
+    let mut written = 0;
+    for (start, end) in spans {
+        let Some((before, keyword, _)) = split_at_span(line, start.max(written), end) else {
+            continue;
+        };
+        out.push_str(&before[written.min(before.len())..]);
+        out.push_str(HIGHLIGHT);
+        out.push_str(keyword);
+        out.push_str(RESET);
+        written = end;
+    }
+    out.push_str(&line[written.min(line.len())..]);
+    out
+}
+
+// Character columns (from 1) of byte offsets in `line`.
+pub fn column(line: &str, offset: usize) -> usize {
+    line.get(..offset)
+        .map_or(0, |before| before.chars().count())
+        + 1
+}
+
+fn split_at_span(text: &str, start: usize, end: usize) -> Option<(&str, &str, &str)> {
+    Some((text.get(..start)?, text.get(start..end)?, text.get(end..)?))
+}
+
+// Tabs and other whitespace would break the alignment.
+fn flatten_whitespace(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect()
+}
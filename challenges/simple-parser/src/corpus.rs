@@ -0,0 +1,165 @@
+use rust_coding_challenges::encoding::Encoding;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use crate::{open_file, Index, Normalizer, Query};
+
+pub type DocId = usize;
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub name: String,
+    pub index: Index,
+}
+
+// A set of documents indexed with the same normalizer. Each document keeps
+// its own `Index`, so adding or removing a document never touches the
+// others; the merged postings of a term are (document id, line) pairs,
+// grouped by document. Ids are handed out in insertion order and never reused.
+#[derive(Debug, Clone, Default)]
+pub struct Corpus {
+    documents: BTreeMap<DocId, Document>,
+    next_id: DocId,
+    normalizer: Normalizer,
+    has_positions: bool,
+}
+
+impl Corpus {
+    // A corpus whose documents keep the lines of every term only.
+    pub fn new(normalizer: Normalizer) -> Self {
+        Corpus {
+            normalizer,
+            ..Corpus::default()
+        }
+    }
+
+    // A corpus whose documents are indexed with positions, see
+    // `Index::build_with_positions`.
+    pub fn with_positions(normalizer: Normalizer) -> Self {
+        Corpus {
+            normalizer,
+            has_positions: true,
+            ..Corpus::default()
+        }
+    }
+
+    pub fn normalizer(&self) -> &Normalizer {
+        &self.normalizer
+    }
+
+    // Indexes a document under `name`. A document that already has this name
+    // is replaced and keeps its id.
+    pub fn add_document<R: BufRead>(&mut self, name: &str, reader: R) -> io::Result<DocId> {
+        let index = match self.has_positions {
+            true => Index::build_with_positions(reader, &self.normalizer)?,
+            false => Index::build(reader, &self.normalizer)?,
+        };
+        let id = self.find(name).unwrap_or_else(|| {
+            self.next_id += 1;
+            self.next_id - 1
+        });
+        self.documents.insert(
+            id,
+            Document {
+                name: name.to_string(),
+                index,
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn add_file(&mut self, file_path: &str, encoding: Option<Encoding>) -> io::Result<DocId> {
+        self.add_document(file_path, open_file(file_path, encoding)?)
+    }
+
+    // Adds a single file, or every file below a directory, see `files_below`.
+    pub fn add_path(&mut self, path: &str, encoding: Option<Encoding>) -> io::Result<Vec<DocId>> {
+        files_below(path)?
+            .iter()
+            .map(|file| self.add_file(file, encoding))
+            .collect()
+    }
+
+    pub fn remove_document(&mut self, id: DocId) -> Option<Document> {
+        self.documents.remove(&id)
+    }
+
+    pub fn document(&self, id: DocId) -> Option<&Document> {
+        self.documents.get(&id)
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = (DocId, &Document)> + '_ {
+        self.documents.iter().map(|(&id, document)| (id, document))
+    }
+
+    pub fn find(&self, name: &str) -> Option<DocId> {
+        self.documents()
+            .find(|(_, document)| document.name == name)
+            .map(|(id, _)| id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    pub fn postings(&self, term: &str) -> BTreeMap<DocId, BTreeSet<usize>> {
+        self.documents()
+            .map(|(id, document)| (id, document.index.lines(term)))
+            .filter(|(_, lines)| !lines.is_empty())
+            .collect()
+    }
+
+    pub fn surface_forms(&self, term: &str) -> BTreeSet<String> {
+        self.documents()
+            .flat_map(|(_, document)| document.index.surface_forms(term))
+            .collect()
+    }
+
+    // All terms of the corpus in `solve` order with their postings.
+    pub fn word_map(&self) -> BTreeMap<String, BTreeMap<DocId, BTreeSet<usize>>> {
+        let mut word_map: BTreeMap<String, BTreeMap<DocId, BTreeSet<usize>>> = BTreeMap::new();
+        for (id, document) in self.documents() {
+            for (term, lines) in document.index.to_word_map() {
+                word_map.entry(term).or_default().insert(id, lines);
+            }
+        }
+        word_map
+    }
+
+    pub fn query(&self, query: &Query) -> BTreeMap<DocId, BTreeSet<usize>> {
+        self.documents()
+            .map(|(id, document)| (id, query.evaluate(&document.index)))
+            .filter(|(_, lines)| !lines.is_empty())
+            .collect()
+    }
+}
+
+// The path itself if it is not a directory, or every file below it in path
+// order. Hidden files and directories are skipped, and so are symbolic links
+// to directories, which could lead back to a directory being walked.
+pub fn files_below(path: &str) -> io::Result<Vec<String>> {
+    if !Path::new(path).is_dir() {
+        return Ok(vec![path.to_string()]);
+    }
+    let mut entries: Vec<_> = fs::read_dir(path)?
+        .map(|entry| entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))))
+        .collect::<io::Result<_>>()?;
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut files = Vec::new();
+    for (entry, file_type) in entries {
+        let hidden = entry
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        let linked_dir = file_type.is_symlink() && entry.is_dir();
+        if !hidden && !linked_dir {
+            files.extend(files_below(&entry.to_string_lossy())?);
+        }
+    }
+    Ok(files)
+}
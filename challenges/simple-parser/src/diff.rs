@@ -0,0 +1,129 @@
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::LineRanges;
+
+// A word that is in both versions but on different lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostingChange {
+    pub old: BTreeSet<usize>,
+    pub new: BTreeSet<usize>,
+}
+
+impl PostingChange {
+    pub fn added_lines(&self) -> BTreeSet<usize> {
+        self.new.difference(&self.old).copied().collect()
+    }
+
+    pub fn removed_lines(&self) -> BTreeSet<usize> {
+        self.old.difference(&self.new).copied().collect()
+    }
+}
+
+// The difference between the word maps of two versions of a text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexDiff {
+    pub added: BTreeMap<String, BTreeSet<usize>>,
+    pub removed: BTreeMap<String, BTreeSet<usize>>,
+    pub changed: BTreeMap<String, PostingChange>,
+}
+
+// Walks both maps in order at the same time, so words present in both are
+// compared without a lookup.
+pub fn diff_word_maps(
+    old: &BTreeMap<String, BTreeSet<usize>>,
+    new: &BTreeMap<String, BTreeSet<usize>>,
+) -> IndexDiff {
+    let mut diff = IndexDiff::default();
+    let mut old_words = old.iter().peekable();
+    let mut new_words = new.iter().peekable();
+    loop {
+        let order = match (old_words.peek(), new_words.peek()) {
+            (None, None) => break,
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (Some((old_word, _)), Some((new_word, _))) => old_word.cmp(new_word),
+        };
+        match order {
+            std::cmp::Ordering::Less => {
+                let (word, lines) = old_words.next().unwrap();
+                diff.removed.insert(word.clone(), lines.clone());
+            }
+            std::cmp::Ordering::Greater => {
+                let (word, lines) = new_words.next().unwrap();
+                diff.added.insert(word.clone(), lines.clone());
+            }
+            std::cmp::Ordering::Equal => {
+                let (word, old_lines) = old_words.next().unwrap();
+                let (_, new_lines) = new_words.next().unwrap();
+                if old_lines != new_lines {
+                    let change = PostingChange {
+                        old: old_lines.clone(),
+                        new: new_lines.clone(),
+                    };
+                    diff.changed.insert(word.clone(), change);
+                }
+            }
+        }
+    }
+    diff
+}
+
+impl IndexDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    // One line per word: `+` added, `-` removed, `~` moved, then a summary.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (word, lines) in &self.added {
+            out.push_str(&format!("+ {}: {}\n", word, LineRanges::new(lines)));
+        }
+        for (word, lines) in &self.removed {
+            out.push_str(&format!("- {}: {}\n", word, LineRanges::new(lines)));
+        }
+        for (word, change) in &self.changed {
+            out.push_str(&format!(
+                "~ {}: {} -> {}\n",
+                word,
+                LineRanges::new(&change.old),
+                LineRanges::new(&change.new)
+            ));
+        }
+        out.push_str(&format!(
+            "{} added, {} removed, {} changed\n",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        ));
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let changed: BTreeMap<&String, serde_json::Value> = self
+            .changed
+            .iter()
+            .map(|(word, change)| {
+                let value = json!({
+                    "old": change.old,
+                    "new": change.new,
+                    "added_lines": change.added_lines(),
+                    "removed_lines": change.removed_lines(),
+                });
+                (word, value)
+            })
+            .collect();
+        let value = json!({
+            "added": self.added,
+            "removed": self.removed,
+            "changed": changed,
+            "summary": {
+                "added": self.added.len(),
+                "removed": self.removed.len(),
+                "changed": self.changed.len(),
+            },
+        });
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+}
@@ -0,0 +1,422 @@
+use crate::code::{split_code, CodeState, Language};
+
+// How the lines of an input are cut into the parts that get indexed. Plain
+// text is one part per line; the structured formats give each part a field
+// name, so its words are indexed a second time as `field:word`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputFormat {
+    #[default]
+    Text,
+    // One row per line, the first row naming the columns. Quoted fields may
+    // contain the delimiter but not line breaks.
+    Csv,
+    Tsv,
+    // One JSON value per line; scalars are named by their path, e.g.
+    // `user.name`. Array elements share the path of the array.
+    JsonLines,
+    // Text, without fenced code blocks and link URLs.
+    Markdown,
+    // Source code: identifiers and their snake_case and camelCase parts,
+    // without the keywords of the language. Comments and string literals are
+    // left out, or indexed only as `comment:word` and `string:word`.
+    Code {
+        language: Language,
+        comments: bool,
+        strings: bool,
+    },
+}
+
+impl InputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(InputFormat::Text),
+            "csv" => Some(InputFormat::Csv),
+            "tsv" => Some(InputFormat::Tsv),
+            "jsonl" | "ndjson" => Some(InputFormat::JsonLines),
+            "markdown" | "md" => Some(InputFormat::Markdown),
+            "code" => Some(InputFormat::Code {
+                language: Language::default(),
+                comments: false,
+                strings: false,
+            }),
+            _ => None,
+        }
+    }
+
+    // Whether how a line is split depends on the lines before it: the CSV
+    // header, or being inside a Markdown code fence or a block comment. Such
+    // inputs cannot be indexed in independent pieces.
+    pub fn is_stateful(self) -> bool {
+        !matches!(self, InputFormat::Text | InputFormat::JsonLines)
+    }
+}
+
+// A part of a line: the byte range `start..end` of the line and, for
+// structured formats, the name of the field it belongs to. Words of a named
+// field are indexed plain as well unless `plain` is false.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub start: usize,
+    pub end: usize,
+    pub name: Option<String>,
+    pub plain: bool,
+}
+
+impl Field {
+    pub fn text(start: usize, end: usize) -> Self {
+        Field {
+            start,
+            end,
+            name: None,
+            plain: true,
+        }
+    }
+
+    pub fn named(start: usize, end: usize, name: String) -> Self {
+        Field {
+            start,
+            end,
+            name: Some(name),
+            plain: true,
+        }
+    }
+}
+
+// Splits the lines of one input, in order, into fields. It keeps what the
+// format needs to remember between lines, so every document needs its own.
+#[derive(Debug, Clone, Default)]
+pub struct FieldSplitter {
+    format: InputFormat,
+    // CSV column names, from the first row.
+    header: Option<Vec<String>>,
+    // The opening fence of the Markdown code block the lines are in.
+    fence: Option<(char, usize)>,
+    code: CodeState,
+    fields: Vec<Field>,
+}
+
+impl FieldSplitter {
+    pub fn new(format: InputFormat) -> Self {
+        FieldSplitter {
+            format,
+            ..FieldSplitter::default()
+        }
+    }
+
+    pub fn format(&self) -> InputFormat {
+        self.format
+    }
+
+    // The fields of the next line.
+    pub fn split(&mut self, line: &str) -> &[Field] {
+        let mut fields = std::mem::take(&mut self.fields);
+        fields.clear();
+        match self.format {
+            InputFormat::Text => fields.push(Field::text(0, line.len())),
+            InputFormat::Csv => self.split_row(line, ',', &mut fields),
+            InputFormat::Tsv => self.split_row(line, '\t', &mut fields),
+            InputFormat::JsonLines => split_json(line, &mut fields),
+            InputFormat::Markdown => self.split_markdown(line, &mut fields),
+            InputFormat::Code {
+                language,
+                comments,
+                strings,
+            } => split_code(
+                &mut self.code,
+                language,
+                comments,
+                strings,
+                line,
+                &mut fields,
+            ),
+        }
+        self.fields = fields;
+        &self.fields
+    }
+
+    fn split_row(&mut self, line: &str, delimiter: char, fields: &mut Vec<Field>) {
+        let cells = csv_cells(line.trim_end_matches(['\n', '\r']), delimiter);
+        let Some(header) = &self.header else {
+            // The header row is indexed as plain text.
+            self.header = Some(
+                cells
+                    .iter()
+                    .map(|&(start, end)| field_name(&line[start..end]))
+                    .collect(),
+            );
+            fields.extend(
+                cells
+                    .into_iter()
+                    .map(|(start, end)| Field::text(start, end)),
+            );
+            return;
+        };
+        for (column, (start, end)) in cells.into_iter().enumerate() {
+            let name = match header.get(column) {
+                Some(name) if !name.is_empty() => name.clone(),
+                _ => (column + 1).to_string(),
+            };
+            fields.push(Field::named(start, end, name));
+        }
+    }
+
+    fn split_markdown(&mut self, line: &str, fields: &mut Vec<Field>) {
+        let trimmed = line.trim_start_matches(' ');
+        let fence = trimmed
+            .chars()
+            .next()
+            .filter(|&c| (c == '`' || c == '~') && line.len() - trimmed.len() <= 3)
+            .map(|c| (c, trimmed.chars().take_while(|&d| d == c).count()))
+            .filter(|&(_, len)| len >= 3);
+        match (self.fence, fence) {
+            (None, Some(fence)) => {
+                self.fence = Some(fence);
+                return;
+            }
+            (Some((open, open_len)), Some((c, len)))
+                if c == open && len >= open_len && trimmed[len..].trim().is_empty() =>
+            {
+                self.fence = None;
+                return;
+            }
+            (Some(_), _) => return,
+            (None, None) => {}
+        }
+        let mut start = 0;
+        for (url_start, url_end) in markdown_urls(line) {
+            if url_start > start {
+                fields.push(Field::text(start, url_start));
+            }
+            start = url_end;
+        }
+        fields.push(Field::text(start, line.len()));
+    }
+}
+
+// Column names become one word: "First Name" -> "First_Name".
+fn field_name(cell: &str) -> String {
+    cell.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+// Byte ranges of the cells of a row. A quoted cell's range is what is
+// between the quotes; doubled quotes inside it are left as they are.
+fn csv_cells(row: &str, delimiter: char) -> Vec<(usize, usize)> {
+    let mut cells = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, c) in row.char_indices() {
+        if c == '"' && (in_quotes || row[start..i].trim().is_empty()) {
+            in_quotes = !in_quotes;
+        } else if c == delimiter && !in_quotes {
+            cells.push(unquote(row, start, i));
+            start = i + c.len_utf8();
+        }
+    }
+    cells.push(unquote(row, start, row.len()));
+    cells
+}
+
+fn unquote(row: &str, start: usize, end: usize) -> (usize, usize) {
+    let cell = &row[start..end];
+    let trimmed = cell.trim();
+    if trimmed.len() >= 2 && trimmed.starts_with('"') && trimmed.ends_with('"') {
+        let offset = start + (cell.len() - cell.trim_start().len());
+        (offset + 1, offset + trimmed.len() - 1)
+    } else {
+        (start, end)
+    }
+}
+
+// Ranges of link destinations: `(url)` after `]`, `<scheme:...>` autolinks,
+// and the URL of a `[label]: url` reference definition.
+fn markdown_urls(line: &str) -> Vec<(usize, usize)> {
+    let mut urls = Vec::new();
+    let trimmed = line.trim_start();
+    if let Some(label) = trimmed.strip_prefix('[') {
+        if let Some(close) = label
+            .find(']')
+            .filter(|&close| label[close + 1..].starts_with(':'))
+        {
+            let start = line.len() - label.len() + close + 2;
+            urls.push((start, line.trim_end().len().max(start)));
+            return urls;
+        }
+    }
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let end = match bytes[i] {
+            b'(' if i > 0 && bytes[i - 1] == b']' => line[i..].find(')').map(|close| i + close + 1),
+            b'<' => line[i + 1..]
+                .find('>')
+                .map(|close| i + 1 + close + 1)
+                .filter(|&end| is_autolink(&line[i + 1..end - 1])),
+            _ => None,
+        };
+        match end {
+            Some(end) => {
+                urls.push((i, end));
+                i = end;
+            }
+            None => i += 1,
+        }
+    }
+    urls
+}
+
+fn is_autolink(target: &str) -> bool {
+    !target.contains(char::is_whitespace)
+        && target.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+        })
+}
+
+// A JSON line that does not parse is indexed as plain text.
+fn split_json(line: &str, fields: &mut Vec<Field>) {
+    let mut scanner = JsonScanner {
+        bytes: line.as_bytes(),
+        pos: 0,
+        path: Vec::new(),
+        fields,
+    };
+    scanner.skip_whitespace();
+    let parsed = scanner.value().is_some() && {
+        scanner.skip_whitespace();
+        scanner.pos == scanner.bytes.len()
+    };
+    if !parsed {
+        fields.clear();
+        fields.push(Field::text(0, line.len()));
+    }
+}
+
+// Just enough of a JSON parser to find the byte range and path of every
+// string, number and literal. Escape sequences in strings are left as they
+// are.
+struct JsonScanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    path: Vec<&'a str>,
+    fields: &'a mut Vec<Field>,
+}
+
+impl JsonScanner<'_> {
+    fn value(&mut self) -> Option<()> {
+        match *self.bytes.get(self.pos)? {
+            b'{' => self.object(),
+            b'[' => self.array(),
+            b'"' => {
+                let (start, end) = self.string()?;
+                self.push_field(start, end);
+                Some(())
+            }
+            _ => {
+                let start = self.pos;
+                while self.pos < self.bytes.len() && !b",]} \t\r\n".contains(&self.bytes[self.pos])
+                {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return None;
+                }
+                self.push_field(start, self.pos);
+                Some(())
+            }
+        }
+    }
+
+    fn object(&mut self) -> Option<()> {
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            return Some(());
+        }
+        loop {
+            self.skip_whitespace();
+            let (start, end) = self.string()?;
+            // Keys are ASCII-delimited, so the range is on char boundaries.
+            let key = std::str::from_utf8(&self.bytes[start..end]).ok()?;
+            self.skip_whitespace();
+            if !self.eat(b':') {
+                return None;
+            }
+            self.skip_whitespace();
+            self.path.push(key);
+            self.value()?;
+            self.path.pop();
+            self.skip_whitespace();
+            if self.eat(b'}') {
+                return Some(());
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn array(&mut self) -> Option<()> {
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Some(());
+        }
+        loop {
+            self.skip_whitespace();
+            self.value()?;
+            self.skip_whitespace();
+            if self.eat(b']') {
+                return Some(());
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+
+    // The range between the quotes.
+    fn string(&mut self) -> Option<(usize, usize)> {
+        if !self.eat(b'"') {
+            return None;
+        }
+        let start = self.pos;
+        while self.pos < self.bytes.len() {
+            match self.bytes[self.pos] {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    self.pos += 1;
+                    return Some((start, self.pos - 1));
+                }
+                _ => self.pos += 1,
+            }
+        }
+        None
+    }
+
+    fn push_field(&mut self, start: usize, end: usize) {
+        self.fields.push(match self.path.is_empty() {
+            true => Field::text(start, end),
+            false => Field::named(start, end, field_name(&self.path.join("."))),
+        });
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let matched = self.bytes.get(self.pos) == Some(&byte);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+    }
+}
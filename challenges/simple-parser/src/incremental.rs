@@ -0,0 +1,187 @@
+use rust_coding_challenges::encoding::Encoding;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::io;
+
+use crate::{read_file_with_encoding, LineBuffers, Normalizer};
+
+// What an update did: lines `first_line..first_line + removed` of the old
+// text were replaced by `inserted` new lines starting at the same number, and
+// all later lines moved by `inserted - removed`. `terms` are the words of the
+// removed and inserted lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Change {
+    pub first_line: usize,
+    pub removed: usize,
+    pub inserted: usize,
+    pub terms: BTreeSet<String>,
+}
+
+impl Change {
+    pub fn is_empty(&self) -> bool {
+        self.removed == 0 && self.inserted == 0
+    }
+}
+
+// The `solve` word map of a text that changes over time. Every line is
+// remembered by a hash and by its distinct terms; an update compares the
+// hashes of the new text with the old ones, and only the lines between the
+// unchanged start and the unchanged end are re-indexed. Lines after them are
+// renumbered through their remembered terms, so the rest of the map is never
+// touched. Appending lines is the case where the unchanged end is empty.
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalIndex {
+    word_map: BTreeMap<String, BTreeSet<usize>>,
+    line_hashes: Vec<u64>,
+    line_terms: Vec<Vec<String>>,
+    normalizer: Normalizer,
+}
+
+impl IncrementalIndex {
+    pub fn new(normalizer: Normalizer) -> Self {
+        IncrementalIndex {
+            normalizer,
+            ..IncrementalIndex::default()
+        }
+    }
+
+    pub fn from_text(content: &str, normalizer: Normalizer) -> Self {
+        let mut index = IncrementalIndex::new(normalizer);
+        index.update(content);
+        index
+    }
+
+    pub fn word_map(&self) -> &BTreeMap<String, BTreeSet<usize>> {
+        &self.word_map
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_hashes.len()
+    }
+
+    pub fn update(&mut self, content: &str) -> Change {
+        // Lines as `BufRead::read_line` sees them, newline included.
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let hashes: Vec<u64> = lines.iter().map(|line| hash_line(line)).collect();
+
+        // A line of a stateful format, e.g. a CSV header, can change how all
+        // later lines are indexed, so any change re-indexes the whole text.
+        let (prefix, suffix) = if self.normalizer.format.is_stateful() && hashes != self.line_hashes
+        {
+            (0, 0)
+        } else {
+            let prefix = self
+                .line_hashes
+                .iter()
+                .zip(&hashes)
+                .take_while(|(old, new)| old == new)
+                .count();
+            let suffix = self.line_hashes[prefix..]
+                .iter()
+                .rev()
+                .zip(hashes[prefix..].iter().rev())
+                .take_while(|(old, new)| old == new)
+                .count();
+            (prefix, suffix)
+        };
+        let removed = self.line_hashes.len() - prefix - suffix;
+        let inserted = hashes.len() - prefix - suffix;
+        let mut change = Change {
+            first_line: prefix + 1,
+            removed,
+            inserted,
+            terms: BTreeSet::new(),
+        };
+        if change.is_empty() {
+            return change;
+        }
+
+        for (i, terms) in self.line_terms[prefix..prefix + removed].iter().enumerate() {
+            for term in terms {
+                remove_posting(&mut self.word_map, term, prefix + i + 1);
+                change.terms.insert(term.clone());
+            }
+        }
+        self.renumber(prefix + removed, inserted as isize - removed as isize);
+
+        let mut buffers = LineBuffers::default();
+        let mut splitter = self.normalizer.field_splitter();
+        let new_terms: Vec<Vec<String>> = lines[prefix..prefix + inserted]
+            .iter()
+            .map(|line| {
+                let mut terms = BTreeSet::new();
+                self.normalizer
+                    .line_terms(&mut splitter, line, &mut buffers, |term| {
+                        terms.insert(term.to_string());
+                    });
+                terms.into_iter().collect()
+            })
+            .collect();
+        for (i, terms) in new_terms.iter().enumerate() {
+            for term in terms {
+                self.word_map
+                    .entry(term.clone())
+                    .or_default()
+                    .insert(prefix + i + 1);
+                change.terms.insert(term.clone());
+            }
+        }
+
+        self.line_hashes.splice(
+            prefix..prefix + removed,
+            hashes[prefix..prefix + inserted].iter().copied(),
+        );
+        self.line_terms.splice(prefix..prefix + removed, new_terms);
+        change
+    }
+
+    pub fn update_file(
+        &mut self,
+        file_path: &str,
+        encoding: Option<Encoding>,
+    ) -> io::Result<Change> {
+        Ok(self.update(&read_file_with_encoding(file_path, encoding)?))
+    }
+
+    // Moves the lines after the first `start` lines by `delta`: all their
+    // postings are taken out first and put back under the new numbers, so
+    // moved lines never collide with lines not yet moved.
+    fn renumber(&mut self, start: usize, delta: isize) {
+        if delta == 0 {
+            return;
+        }
+        for (i, terms) in self.line_terms[start..].iter().enumerate() {
+            for term in terms {
+                if let Some(lines) = self.word_map.get_mut(term) {
+                    lines.remove(&(start + i + 1));
+                }
+            }
+        }
+        for (i, terms) in self.line_terms[start..].iter().enumerate() {
+            let line = (start + i + 1)
+                .checked_add_signed(delta)
+                .expect("moved lines stay after the change");
+            for term in terms {
+                self.word_map.entry(term.clone()).or_default().insert(line);
+            }
+        }
+    }
+}
+
+fn remove_posting(word_map: &mut BTreeMap<String, BTreeSet<usize>>, term: &str, line: usize) {
+    if let Some(lines) = word_map.get_mut(term) {
+        lines.remove(&line);
+        if lines.is_empty() {
+            word_map.remove(term);
+        }
+    }
+}
+
+// Hash collisions would make a changed line look unchanged; with 64 bit
+// hashes that is not a practical concern.
+fn hash_line(line: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    line.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead};
+
+use crate::{prefix_terms, LineBuffers, Normalizer};
+
+// Where a term occurs: the line, its position among the terms of the line
+// and its byte range `start..end` in the original line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Occurrence {
+    pub line: usize,
+    pub position: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Word index: for every term, the lines it appears in. Built with positions,
+// it also remembers where in a line each term occurs: per line, the positions
+// of the term among the terms of that line (starting at 0). Positions are
+// what phrase queries need, and together with the number of terms per line
+// they give the term frequencies and lengths used for ranking. The byte range
+// of every term in its line is kept as well, to show occurrences in context.
+// Without positions all of these are empty and only the lines are kept.
+// `to_word_map` gives back the plain `solve` map.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Index {
+    // Line number -> positions of the term, empty without positions.
+    postings: BTreeMap<String, BTreeMap<usize, Vec<usize>>>,
+    // Per line, the byte range of every term in the original line, in
+    // position order; empty without positions.
+    word_spans: Vec<Vec<(usize, usize)>>,
+    // With stemming, the words of the text that were reduced to each term.
+    surface_forms: BTreeMap<String, BTreeSet<String>>,
+    normalizer: Normalizer,
+    has_positions: bool,
+}
+
+impl Index {
+    // An index of the lines of every term only, see `build_with_positions`.
+    pub fn build<R: BufRead>(reader: R, normalizer: &Normalizer) -> io::Result<Self> {
+        Index::build_with(reader, normalizer, false)
+    }
+
+    // An index that also keeps positions, term frequencies, line lengths and
+    // byte ranges, for phrase queries, ranking and showing occurrences.
+    pub fn build_with_positions<R: BufRead>(
+        reader: R,
+        normalizer: &Normalizer,
+    ) -> io::Result<Self> {
+        Index::build_with(reader, normalizer, true)
+    }
+
+    fn build_with<R: BufRead>(
+        mut reader: R,
+        normalizer: &Normalizer,
+        has_positions: bool,
+    ) -> io::Result<Self> {
+        let mut index = Index {
+            normalizer: normalizer.clone(),
+            has_positions,
+            ..Index::default()
+        };
+        let mut line = String::new();
+        let mut buffers = LineBuffers::default();
+        let mut splitter = normalizer.field_splitter();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let line_number = index.word_spans.len() + 1;
+            // Only the terms are needed, which saves building tokens.
+            if !has_positions && !normalizer.stem {
+                normalizer.line_terms(&mut splitter, &line, &mut buffers, |term| {
+                    match index.postings.get_mut(term) {
+                        Some(lines) => {
+                            lines.entry(line_number).or_default();
+                        }
+                        None => {
+                            index.postings.insert(
+                                term.to_string(),
+                                BTreeMap::from([(line_number, Vec::new())]),
+                            );
+                        }
+                    }
+                });
+                index.word_spans.push(Vec::new());
+                continue;
+            }
+            let mut spans = Vec::new();
+            for (position, token) in normalizer
+                .line_tokens(&mut splitter, &line)
+                .into_iter()
+                .enumerate()
+            {
+                if normalizer.stem
+                    && !index
+                        .surface_forms
+                        .get(&token.term)
+                        .is_some_and(|forms| forms.contains(&token.surface))
+                {
+                    index
+                        .surface_forms
+                        .entry(token.term.clone())
+                        .or_default()
+                        .insert(token.surface);
+                }
+                let lines = match index.postings.get_mut(&token.term) {
+                    Some(lines) => lines,
+                    None => index.postings.entry(token.term).or_default(),
+                };
+                let positions = lines.entry(line_number).or_default();
+                if has_positions {
+                    positions.push(position);
+                    spans.push((token.start, token.end));
+                }
+            }
+            index.word_spans.push(spans);
+        }
+        Ok(index)
+    }
+
+    pub fn from_text(content: &str, normalizer: &Normalizer) -> Self {
+        Index::build(content.as_bytes(), normalizer).expect("reading from a string cannot fail")
+    }
+
+    pub fn from_text_with_positions(content: &str, normalizer: &Normalizer) -> Self {
+        Index::build_with_positions(content.as_bytes(), normalizer)
+            .expect("reading from a string cannot fail")
+    }
+
+    pub fn has_positions(&self) -> bool {
+        self.has_positions
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.word_spans.len()
+    }
+
+    // Number of terms in a line; this and everything below that counts or
+    // places single occurrences needs an index built with positions.
+    pub fn line_length(&self, line: usize) -> usize {
+        self.line_spans(line).len()
+    }
+
+    // Number of terms in the whole text.
+    pub fn total_terms(&self) -> usize {
+        self.word_spans.iter().map(Vec::len).sum()
+    }
+
+    // Byte ranges of the terms of a line, in position order.
+    pub fn line_spans(&self, line: usize) -> &[(usize, usize)] {
+        line.checked_sub(1)
+            .and_then(|i| self.word_spans.get(i))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    // Every occurrence of `term`, in text order.
+    pub fn occurrences(&self, term: &str) -> Vec<Occurrence> {
+        self.postings
+            .get(term)
+            .into_iter()
+            .flatten()
+            .flat_map(|(&line, positions)| {
+                positions.iter().map(move |&position| {
+                    let (start, end) = self.line_spans(line)[position];
+                    Occurrence {
+                        line,
+                        position,
+                        start,
+                        end,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    // How often `term` occurs in `line`.
+    pub fn term_frequency(&self, term: &str, line: usize) -> usize {
+        self.postings
+            .get(term)
+            .and_then(|lines| lines.get(&line))
+            .map_or(0, Vec::len)
+    }
+
+    // How often `term` occurs in the whole text.
+    pub fn term_count(&self, term: &str) -> usize {
+        self.postings
+            .get(term)
+            .map_or(0, |lines| lines.values().map(Vec::len).sum())
+    }
+
+    pub fn normalizer(&self) -> &Normalizer {
+        &self.normalizer
+    }
+
+    // The words of the text indexed under `term` when stemming, e.g.
+    // "programs" and "programming" for "program". Without stemming a term is
+    // its own only surface form.
+    pub fn surface_forms(&self, term: &str) -> BTreeSet<String> {
+        if !self.normalizer.stem {
+            return self
+                .postings
+                .get(term)
+                .map(|_| BTreeSet::from([term.to_string()]))
+                .unwrap_or_default();
+        }
+        self.surface_forms.get(term).cloned().unwrap_or_default()
+    }
+
+    pub fn lines(&self, term: &str) -> BTreeSet<usize> {
+        self.postings
+            .get(term)
+            .map(|lines| lines.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    // Line number -> positions of `term` within that line.
+    pub fn positions(&self, term: &str) -> Option<&BTreeMap<usize, Vec<usize>>> {
+        self.postings.get(term)
+    }
+
+    // Position of the first occurrence of `term` in `line`.
+    pub fn first_position(&self, term: &str, line: usize) -> Option<usize> {
+        self.postings.get(term)?.get(&line)?.first().copied()
+    }
+
+    pub fn terms(&self) -> impl Iterator<Item = &str> + '_ {
+        self.postings.keys().map(String::as_str)
+    }
+
+    pub fn terms_with_prefix(&self, prefix: &str) -> Vec<&str> {
+        prefix_terms(&self.postings, prefix)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    pub fn to_word_map(&self) -> BTreeMap<String, BTreeSet<usize>> {
+        self.postings
+            .iter()
+            .map(|(term, lines)| (term.clone(), lines.keys().copied().collect()))
+            .collect()
+    }
+
+    // Lines in which `terms` occur next to each other, in this order.
+    pub fn phrase_lines(&self, terms: &[String]) -> BTreeSet<usize> {
+        let Some((first, rest)) = terms.split_first() else {
+            return BTreeSet::new();
+        };
+        let Some(first_lines) = self.postings.get(first) else {
+            return BTreeSet::new();
+        };
+        let rest: Option<Vec<&BTreeMap<usize, Vec<usize>>>> =
+            rest.iter().map(|term| self.postings.get(term)).collect();
+        let Some(rest) = rest else {
+            return BTreeSet::new();
+        };
+        first_lines
+            .iter()
+            .filter(|(line, starts)| {
+                starts.iter().any(|&start| {
+                    rest.iter().enumerate().all(|(offset, lines)| {
+                        lines.get(line).is_some_and(|positions| {
+                            positions.binary_search(&(start + offset + 1)).is_ok()
+                        })
+                    })
+                })
+            })
+            .map(|(&line, _)| line)
+            .collect()
+    }
+}
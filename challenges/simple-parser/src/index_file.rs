@@ -0,0 +1,444 @@
+use memmap2::Mmap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{InputFormat, Language, Normalizer};
+
+// Layout of an index file, all integers little endian:
+//
+//   header   magic "SPIX", version u16, normalizer flags u16, term count u64,
+//            string region length u64, CRC-32 of everything after the header u32,
+//            stopword region length u32
+//   terms    one 24 byte entry per term, sorted by term:
+//            string offset u64, postings offset u64, string length u32, postings length u32
+//   strings  the UTF-8 bytes of all terms
+//   postings per term: number of lines, first line, then the gaps between
+//            consecutive lines, each as an unsigned LEB128 varint
+//   stopwords the sorted stopwords of the normalizer, each as a varint byte
+//            length followed by its UTF-8 bytes
+//
+// The term table has fixed size entries so a term is found by binary search
+// directly in the memory-mapped file. The stopwords are stored so the index
+// normalizes lookups exactly like the text it was built from.
+//
+// The version changes whenever the layout or the meaning of the flags does;
+// version 1 had no input format or code bits, version 2 no possessive bit and
+// version 3 no stopwords.
+const MAGIC: &[u8; 4] = b"SPIX";
+const VERSION: u16 = 4;
+const HEADER_LEN: usize = 32;
+const ENTRY_LEN: usize = 24;
+
+const STRIP_PUNCTUATION: u16 = 1;
+const FOLD_APOSTROPHES: u16 = 1 << 1;
+const SPLIT_HYPHENS: u16 = 1 << 2;
+const CASE_FOLD: u16 = 1 << 3;
+const STEM: u16 = 1 << 4;
+// Bits 5-7 hold the input format, so lookups parse `field:word` the same way,
+// bits 8 and 9 whether code comments and strings were indexed and bits 10-13
+// the language of the code.
+const FORMAT_SHIFT: u16 = 5;
+const CODE_COMMENTS: u16 = 1 << 8;
+const CODE_STRINGS: u16 = 1 << 9;
+const LANGUAGE_SHIFT: u16 = 10;
+const STRIP_POSSESSIVES: u16 = 1 << 14;
+// Every bit a file of this version may set.
+const KNOWN_FLAGS: u16 = (1 << 15) - 1;
+
+pub fn write_index<W: Write>(
+    word_map: &BTreeMap<String, BTreeSet<usize>>,
+    normalizer: &Normalizer,
+    mut writer: W,
+) -> io::Result<()> {
+    let mut terms = Vec::with_capacity(word_map.len() * ENTRY_LEN);
+    let mut strings = Vec::new();
+    let mut postings = Vec::new();
+    for (word, lines) in word_map {
+        let postings_start = postings.len();
+        write_varint(&mut postings, lines.len() as u64);
+        let mut previous = 0;
+        for &line in lines {
+            write_varint(&mut postings, (line - previous) as u64);
+            previous = line;
+        }
+        terms.extend_from_slice(&(strings.len() as u64).to_le_bytes());
+        terms.extend_from_slice(&(postings_start as u64).to_le_bytes());
+        terms.extend_from_slice(&(word.len() as u32).to_le_bytes());
+        terms.extend_from_slice(&((postings.len() - postings_start) as u32).to_le_bytes());
+        strings.extend_from_slice(word.as_bytes());
+    }
+    let mut stopwords = Vec::new();
+    for word in normalizer.stopwords.iter() {
+        write_varint(&mut stopwords, word.len() as u64);
+        stopwords.extend_from_slice(word.as_bytes());
+    }
+    let stopwords_len = u32::try_from(stopwords.len())
+        .map_err(|_| invalid_data("too many stopwords for an index file"))?;
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&terms);
+    hasher.update(&strings);
+    hasher.update(&postings);
+    hasher.update(&stopwords);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&normalizer_flags(normalizer).to_le_bytes());
+    header.extend_from_slice(&(word_map.len() as u64).to_le_bytes());
+    header.extend_from_slice(&(strings.len() as u64).to_le_bytes());
+    header.extend_from_slice(&hasher.finalize().to_le_bytes());
+    header.extend_from_slice(&stopwords_len.to_le_bytes());
+
+    writer.write_all(&header)?;
+    writer.write_all(&terms)?;
+    writer.write_all(&strings)?;
+    writer.write_all(&postings)?;
+    writer.write_all(&stopwords)?;
+    writer.flush()
+}
+
+pub fn save_index<P: AsRef<Path>>(
+    word_map: &BTreeMap<String, BTreeSet<usize>>,
+    normalizer: &Normalizer,
+    index_path: P,
+) -> io::Result<()> {
+    write_index(
+        word_map,
+        normalizer,
+        BufWriter::new(File::create(index_path)?),
+    )
+}
+
+// Whether the file starts like an index file, so commands taking either a text
+// or a saved index can tell them apart. Unreadable files are not index files.
+pub fn is_index_file<P: AsRef<Path>>(path: P) -> bool {
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == MAGIC
+}
+
+// A read-only view of an index file. Terms and postings are decoded from the
+// memory map on demand, so opening a large index costs only the checksum pass.
+pub struct MappedIndex {
+    mmap: Mmap,
+    term_count: usize,
+    strings_start: usize,
+    postings_start: usize,
+    stopwords_start: usize,
+    normalizer: Normalizer,
+}
+
+impl MappedIndex {
+    pub fn open<P: AsRef<Path>>(index_path: P) -> io::Result<Self> {
+        let file = File::open(index_path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < HEADER_LEN || &mmap[..4] != MAGIC {
+            return Err(invalid_data("not a simple-parser index file"));
+        }
+        let version = u16::from_le_bytes(mmap[4..6].try_into().unwrap());
+        if version != VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported index version {}",
+                version
+            )));
+        }
+        let flags = u16::from_le_bytes(mmap[6..8].try_into().unwrap());
+        let term_count = read_u64(&mmap, 8) as usize;
+        let strings_len = read_u64(&mmap, 16) as usize;
+        let checksum = u32::from_le_bytes(mmap[24..28].try_into().unwrap());
+        let stopwords_len = read_u32(&mmap, 28) as usize;
+        if crc32fast::hash(&mmap[HEADER_LEN..]) != checksum {
+            return Err(invalid_data("index file checksum mismatch"));
+        }
+
+        let strings_start = term_count
+            .checked_mul(ENTRY_LEN)
+            .and_then(|len| len.checked_add(HEADER_LEN))
+            .ok_or_else(|| invalid_data("index file is truncated"))?;
+        let postings_start = strings_start
+            .checked_add(strings_len)
+            .filter(|&start| start <= mmap.len())
+            .ok_or_else(|| invalid_data("index file is truncated"))?;
+        let stopwords_start = mmap
+            .len()
+            .checked_sub(stopwords_len)
+            .filter(|&start| start >= postings_start)
+            .ok_or_else(|| invalid_data("index file is truncated"))?;
+        let mut normalizer = normalizer_from_flags(flags)
+            .ok_or_else(|| invalid_data(&format!("unknown index flags {:#06x}", flags)))?;
+        normalizer.stopwords = Arc::new(
+            decode_stopwords(&mmap[stopwords_start..])
+                .ok_or_else(|| invalid_data("index stopwords are not UTF-8 strings"))?,
+        );
+        let index = MappedIndex {
+            mmap,
+            term_count,
+            strings_start,
+            postings_start,
+            stopwords_start,
+            normalizer,
+        };
+        index.validate_entries()?;
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.term_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.term_count == 0
+    }
+
+    // The normalizer the index was built with, stopwords included; search
+    // terms should go through it too.
+    pub fn normalizer(&self) -> &Normalizer {
+        &self.normalizer
+    }
+
+    pub fn get(&self, word: &str) -> Option<BTreeSet<usize>> {
+        let mut low = 0;
+        let mut high = self.term_count;
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.term(mid).as_bytes().cmp(word.as_bytes()) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(self.lines(mid)),
+            }
+        }
+        None
+    }
+
+    pub fn terms(&self) -> impl Iterator<Item = &str> + '_ {
+        (0..self.term_count).map(|i| self.term(i))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, BTreeSet<usize>)> + '_ {
+        (0..self.term_count).map(|i| (self.term(i), self.lines(i)))
+    }
+
+    pub fn to_word_map(&self) -> BTreeMap<String, BTreeSet<usize>> {
+        self.iter()
+            .map(|(term, lines)| (term.to_string(), lines))
+            .collect()
+    }
+
+    fn entry(&self, i: usize) -> (usize, usize, usize, usize) {
+        let start = HEADER_LEN + i * ENTRY_LEN;
+        let string_offset = read_u64(&self.mmap, start) as usize;
+        let postings_offset = read_u64(&self.mmap, start + 8) as usize;
+        let string_len = read_u32(&self.mmap, start + 16) as usize;
+        let postings_len = read_u32(&self.mmap, start + 20) as usize;
+        (string_offset, string_len, postings_offset, postings_len)
+    }
+
+    fn term(&self, i: usize) -> &str {
+        let (offset, len, _, _) = self.entry(i);
+        let start = self.strings_start + offset;
+        // Checked by `validate_entries` when the file was opened.
+        unsafe { std::str::from_utf8_unchecked(&self.mmap[start..start + len]) }
+    }
+
+    fn lines(&self, i: usize) -> BTreeSet<usize> {
+        let (_, _, offset, len) = self.entry(i);
+        let start = self.postings_start + offset;
+        decode_postings(&self.mmap[start..start + len]).unwrap_or_default()
+    }
+
+    fn validate_entries(&self) -> io::Result<()> {
+        let strings_len = self.postings_start - self.strings_start;
+        let postings_len = self.stopwords_start - self.postings_start;
+        let mut previous: Option<&[u8]> = None;
+        for i in 0..self.term_count {
+            let (string_offset, string_len, postings_offset, len) = self.entry(i);
+            if string_offset
+                .checked_add(string_len)
+                .is_none_or(|end| end > strings_len)
+                || postings_offset
+                    .checked_add(len)
+                    .is_none_or(|end| end > postings_len)
+            {
+                return Err(invalid_data("index entry points outside the file"));
+            }
+            let start = self.strings_start + string_offset;
+            let term = &self.mmap[start..start + string_len];
+            if std::str::from_utf8(term).is_err()
+                || previous.is_some_and(|previous| previous >= term)
+            {
+                return Err(invalid_data("index terms are not sorted UTF-8 strings"));
+            }
+            previous = Some(term);
+        }
+        Ok(())
+    }
+}
+
+fn decode_postings(mut bytes: &[u8]) -> Option<BTreeSet<usize>> {
+    let count = read_varint(&mut bytes)?;
+    let mut lines = BTreeSet::new();
+    let mut line = 0;
+    for _ in 0..count {
+        line += read_varint(&mut bytes)? as usize;
+        lines.insert(line);
+    }
+    Some(lines)
+}
+
+fn decode_stopwords(mut bytes: &[u8]) -> Option<BTreeSet<String>> {
+    let mut stopwords = BTreeSet::new();
+    while !bytes.is_empty() {
+        let len = usize::try_from(read_varint(&mut bytes)?).ok()?;
+        let (word, rest) = bytes.split_at_checked(len)?;
+        stopwords.insert(String::from_utf8(word.to_vec()).ok()?);
+        bytes = rest;
+    }
+    Some(stopwords)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        if shift > 63 {
+            return None;
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+fn read_u64(bytes: &[u8], start: usize) -> u64 {
+    u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], start: usize) -> u32 {
+    u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn normalizer_flags(normalizer: &Normalizer) -> u16 {
+    let mut flags = 0;
+    if normalizer.strip_punctuation {
+        flags |= STRIP_PUNCTUATION;
+    }
+    if normalizer.fold_apostrophes {
+        flags |= FOLD_APOSTROPHES;
+    }
+    if normalizer.strip_possessives {
+        flags |= STRIP_POSSESSIVES;
+    }
+    if normalizer.split_hyphens {
+        flags |= SPLIT_HYPHENS;
+    }
+    if normalizer.case_fold {
+        flags |= CASE_FOLD;
+    }
+    if normalizer.stem {
+        flags |= STEM;
+    }
+    flags | format_flags(normalizer.format)
+}
+
+fn format_flags(format: InputFormat) -> u16 {
+    let (number, options) = match format {
+        InputFormat::Text => (0, 0),
+        InputFormat::Csv => (1, 0),
+        InputFormat::Tsv => (2, 0),
+        InputFormat::JsonLines => (3, 0),
+        InputFormat::Markdown => (4, 0),
+        InputFormat::Code {
+            language,
+            comments,
+            strings,
+        } => (
+            5,
+            language_number(language) << LANGUAGE_SHIFT
+                | if comments { CODE_COMMENTS } else { 0 }
+                | if strings { CODE_STRINGS } else { 0 },
+        ),
+    };
+    number << FORMAT_SHIFT | options
+}
+
+fn format_from_flags(flags: u16) -> Option<InputFormat> {
+    Some(match flags >> FORMAT_SHIFT & 0b111 {
+        0 => InputFormat::Text,
+        1 => InputFormat::Csv,
+        2 => InputFormat::Tsv,
+        3 => InputFormat::JsonLines,
+        4 => InputFormat::Markdown,
+        5 => InputFormat::Code {
+            language: language_from_number(flags >> LANGUAGE_SHIFT & 0b1111)?,
+            comments: flags & CODE_COMMENTS != 0,
+            strings: flags & CODE_STRINGS != 0,
+        },
+        _ => return None,
+    })
+}
+
+fn language_number(language: Language) -> u16 {
+    match language {
+        Language::Rust => 0,
+        Language::C => 1,
+        Language::Java => 2,
+        Language::JavaScript => 3,
+        Language::Go => 4,
+        Language::Python => 5,
+        Language::Shell => 6,
+        Language::Toml => 7,
+    }
+}
+
+fn language_from_number(number: u16) -> Option<Language> {
+    Some(match number {
+        0 => Language::Rust,
+        1 => Language::C,
+        2 => Language::Java,
+        3 => Language::JavaScript,
+        4 => Language::Go,
+        5 => Language::Python,
+        6 => Language::Shell,
+        7 => Language::Toml,
+        _ => return None,
+    })
+}
+
+// None for flags this version does not know, e.g. from a newer writer.
+fn normalizer_from_flags(flags: u16) -> Option<Normalizer> {
+    if flags & !KNOWN_FLAGS != 0 {
+        return None;
+    }
+    Some(Normalizer {
+        strip_punctuation: flags & STRIP_PUNCTUATION != 0,
+        fold_apostrophes: flags & FOLD_APOSTROPHES != 0,
+        strip_possessives: flags & STRIP_POSSESSIVES != 0,
+        split_hyphens: flags & SPLIT_HYPHENS != 0,
+        case_fold: flags & CASE_FOLD != 0,
+        stem: flags & STEM != 0,
+        format: format_from_flags(flags)?,
+        ..Normalizer::default()
+    })
+}
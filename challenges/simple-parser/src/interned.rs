@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, BufRead};
+
+use crate::{LineBuffers, LineIndex, Normalizer};
+
+pub type Symbol = u32;
+
+const EMPTY: Symbol = Symbol::MAX;
+
+// Every distinct term stored once, back to back in a single string, and
+// numbered in the order it was first seen. The hash table holds only symbol
+// numbers and compares against the shared string, so interning a term costs
+// no allocation of its own.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    text: String,
+    // End of every symbol in `text`; a symbol starts where the previous one ends.
+    ends: Vec<u32>,
+    // Open addressing with linear probing, `EMPTY` for free slots. The length
+    // is a power of two and at most 3/4 of it is used.
+    slots: Vec<Symbol>,
+    hasher: RandomState,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        let end = self.ends[symbol as usize] as usize;
+        let start = match symbol {
+            0 => 0,
+            _ => self.ends[symbol as usize - 1] as usize,
+        };
+        &self.text[start..end]
+    }
+
+    pub fn get(&self, term: &str) -> Option<Symbol> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        match self.slots[self.find_slot(term)] {
+            EMPTY => None,
+            symbol => Some(symbol),
+        }
+    }
+
+    pub fn intern(&mut self, term: &str) -> Symbol {
+        if (self.len() + 1) * 4 > self.slots.len() * 3 {
+            self.grow();
+        }
+        let slot = self.find_slot(term);
+        if self.slots[slot] != EMPTY {
+            return self.slots[slot];
+        }
+        let symbol = Symbol::try_from(self.len())
+            .ok()
+            .filter(|&symbol| symbol != EMPTY)
+            .expect("too many terms");
+        self.text.push_str(term);
+        self.ends
+            .push(u32::try_from(self.text.len()).expect("terms exceed 4 GiB"));
+        self.slots[slot] = symbol;
+        symbol
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> + '_ {
+        (0..self.len() as Symbol).map(|symbol| (symbol, self.resolve(symbol)))
+    }
+
+    // The slot holding `term`, or the free slot where it belongs.
+    fn find_slot(&self, term: &str) -> usize {
+        let mask = self.slots.len() - 1;
+        let mut slot = self.hasher.hash_one(term) as usize & mask;
+        loop {
+            match self.slots[slot] {
+                EMPTY => return slot,
+                symbol if self.resolve(symbol) == term => return slot,
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+
+    fn grow(&mut self) {
+        let capacity = (self.slots.len() * 2).max(16);
+        self.slots = vec![EMPTY; capacity];
+        for symbol in 0..self.len() as Symbol {
+            let slot = self.find_slot(self.resolve(symbol));
+            self.slots[slot] = symbol;
+        }
+    }
+}
+
+// The `solve` index with interned terms: a symbol table, and per symbol the
+// ascending line numbers as a plain `Vec<u32>`. Instead of a `String` per
+// word and a `BTreeSet` node per line, the memory used is about four bytes per
+// (word, line) pair. The terms of every line are kept as symbols too, which is
+// what phrase queries need; that adds four bytes per token.
+#[derive(Debug, Clone, Default)]
+pub struct InternedIndex {
+    symbols: SymbolTable,
+    postings: Vec<Vec<u32>>,
+    // Symbols sorted by term, for listing in byte order.
+    sorted: Vec<Symbol>,
+    tokens: Vec<Symbol>,
+    // End of every line in `tokens`.
+    line_ends: Vec<u32>,
+}
+
+impl InternedIndex {
+    pub fn build<R: BufRead>(mut reader: R, normalizer: &Normalizer) -> io::Result<Self> {
+        let mut index = InternedIndex::default();
+        let mut line = String::new();
+        let mut buffers = LineBuffers::default();
+        let mut splitter = normalizer.field_splitter();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let line_number = u32::try_from(index.line_ends.len() + 1).expect("too many lines");
+            normalizer.line_terms(&mut splitter, &line, &mut buffers, |term| {
+                let symbol = index.symbols.intern(term);
+                if symbol as usize == index.postings.len() {
+                    index.postings.push(Vec::new());
+                }
+                let lines = &mut index.postings[symbol as usize];
+                if lines.last() != Some(&line_number) {
+                    lines.push(line_number);
+                }
+                index.tokens.push(symbol);
+            });
+            index
+                .line_ends
+                .push(u32::try_from(index.tokens.len()).expect("too many tokens"));
+        }
+        index.postings.iter_mut().for_each(Vec::shrink_to_fit);
+        index.tokens.shrink_to_fit();
+        index.line_ends.shrink_to_fit();
+        index.sorted = (0..index.symbols.len() as Symbol).collect();
+        index
+            .sorted
+            .sort_unstable_by_key(|&symbol| index.symbols.resolve(symbol));
+        Ok(index)
+    }
+
+    pub fn from_text(content: &str, normalizer: &Normalizer) -> Self {
+        InternedIndex::build(content.as_bytes(), normalizer)
+            .expect("reading from a string cannot fail")
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_ends.len()
+    }
+
+    // The lines of a term, ascending.
+    pub fn postings(&self, term: &str) -> &[u32] {
+        match self.symbols.get(term) {
+            Some(symbol) => &self.postings[symbol as usize],
+            None => &[],
+        }
+    }
+
+    pub fn lines(&self, term: &str) -> BTreeSet<usize> {
+        self.postings(term)
+            .iter()
+            .map(|&line| line as usize)
+            .collect()
+    }
+
+    // The terms of a line, in order.
+    pub fn line_symbols(&self, line: usize) -> &[Symbol] {
+        let Some(&end) = self.line_ends.get(line.wrapping_sub(1)) else {
+            return &[];
+        };
+        let start = if line == 1 {
+            0
+        } else {
+            self.line_ends[line - 2]
+        };
+        &self.tokens[start as usize..end as usize]
+    }
+
+    // Terms in byte order, like the keys of the `solve` map.
+    pub fn terms(&self) -> impl Iterator<Item = &str> + '_ {
+        self.sorted
+            .iter()
+            .map(|&symbol| self.symbols.resolve(symbol))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u32])> + '_ {
+        self.sorted.iter().map(|&symbol| {
+            (
+                self.symbols.resolve(symbol),
+                self.postings[symbol as usize].as_slice(),
+            )
+        })
+    }
+
+    pub fn to_word_map(&self) -> BTreeMap<String, BTreeSet<usize>> {
+        self.iter()
+            .map(|(term, lines)| {
+                (
+                    term.to_string(),
+                    lines.iter().map(|&line| line as usize).collect(),
+                )
+            })
+            .collect()
+    }
+
+    // Lines containing all the terms are found by merging their postings,
+    // rarest first; only those lines are scanned for the terms in sequence.
+    pub fn phrase_lines(&self, terms: &[String]) -> BTreeSet<usize> {
+        let symbols: Option<Vec<Symbol>> =
+            terms.iter().map(|term| self.symbols.get(term)).collect();
+        let Some(symbols) = symbols.filter(|symbols| !symbols.is_empty()) else {
+            return BTreeSet::new();
+        };
+        let mut by_length: Vec<&[u32]> = symbols
+            .iter()
+            .map(|&symbol| self.postings[symbol as usize].as_slice())
+            .collect();
+        by_length.sort_unstable_by_key(|lines| lines.len());
+        let (rarest, rest) = by_length.split_first().unwrap();
+        rarest
+            .iter()
+            .filter(|line| rest.iter().all(|lines| lines.binary_search(line).is_ok()))
+            .map(|&line| line as usize)
+            .filter(|&line| {
+                self.line_symbols(line)
+                    .windows(symbols.len())
+                    .any(|window| window == symbols)
+            })
+            .collect()
+    }
+}
+
+impl LineIndex for InternedIndex {
+    fn line_count(&self) -> usize {
+        InternedIndex::line_count(self)
+    }
+
+    fn lines(&self, term: &str) -> BTreeSet<usize> {
+        InternedIndex::lines(self, term)
+    }
+
+    fn phrase_lines(&self, terms: &[String]) -> BTreeSet<usize> {
+        InternedIndex::phrase_lines(self, terms)
+    }
+}
@@ -1,9 +1,58 @@
-use std::collections::{BTreeMap, BTreeSet, HashSet};
-use std::fs;
-use std::io;
+use rust_coding_challenges::encoding::{DecodeReader, Encoding};
+use rust_coding_challenges::utils::read_text_file;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+mod code;
+mod concordance;
+mod corpus;
+mod diff;
+mod format;
+mod incremental;
+mod index;
+mod index_file;
+mod interned;
+mod lookup;
+mod normalize;
+mod order;
+mod parallel;
+mod query;
+mod ranges;
+mod rank;
+mod server;
+mod session;
+mod stats;
+
+pub use code::{identifier_parts, Language, RUST_KEYWORDS};
+pub use concordance::{column, concordance, highlight, KwicLine};
+pub use corpus::{files_below, Corpus, DocId, Document};
+pub use diff::{diff_word_maps, IndexDiff, PostingChange};
+pub use format::{Field, FieldSplitter, InputFormat};
+pub use incremental::{Change, IncrementalIndex};
+pub use index::{Index, Occurrence};
+pub use index_file::{is_index_file, save_index, write_index, MappedIndex};
+pub use interned::{InternedIndex, Symbol, SymbolTable};
+pub use lookup::{
+    default_distance, fuzzy_terms, glob_terms, lookup_fuzzy, lookup_glob, lookup_prefix,
+    prefix_terms,
+};
+pub use normalize::{LineBuffers, Normalizer, Token, ENGLISH_STOPWORDS};
+pub use order::{sorted_entries, sorted_entries_with, Postings, SortOrder};
+pub use parallel::{solve_file_parallel, solve_parallel, solve_parallel_with};
+pub use query::{parse_query, LineIndex, Query};
+pub use ranges::{line_ranges, page, page_count, LineRanges};
+pub use rank::{rank_corpus_lines, rank_documents, rank_lines, Bm25, DocumentHit, LineHit};
+pub use server::{percent_decode, Response, SearchServer};
+pub use session::{Session, SESSION_COMMANDS, SESSION_HELP};
+pub use stats::{TextStats, WordStat, ZipfFit, COVERAGE_PERCENTILES};
 
 pub fn read_file(file_path: &str) -> io::Result<String> {
-    fs::read_to_string(file_path)
+    read_file_with_encoding(file_path, None)
+}
+
+pub fn read_file_with_encoding(file_path: &str, encoding: Option<Encoding>) -> io::Result<String> {
+    read_text_file(file_path, encoding)
 }
 
 pub fn get_lines_lower_case(text: &str) -> Vec<String> {
@@ -12,16 +61,62 @@ pub fn get_lines_lower_case(text: &str) -> Vec<String> {
 
 
 pub fn solve(content: &str) -> BTreeMap<String, BTreeSet<usize>> {
+    solve_with(content, &Normalizer::default())
+}
+
+pub fn solve_with(content: &str, normalizer: &Normalizer) -> BTreeMap<String, BTreeSet<usize>> {
+    solve_reader_with(content.as_bytes(), normalizer).expect("reading from a string cannot fail")
+}
+
+// Builds the same index as `solve` one line at a time, so only the current
+// line is held in memory besides the index itself. The line buffers are
+// reused and a `String` is only allocated for words seen for the first time.
+pub fn solve_reader<R: BufRead>(reader: R) -> io::Result<BTreeMap<String, BTreeSet<usize>>> {
+    solve_reader_with(reader, &Normalizer::default())
+}
+
+pub fn solve_reader_with<R: BufRead>(
+    mut reader: R,
+    normalizer: &Normalizer,
+) -> io::Result<BTreeMap<String, BTreeSet<usize>>> {
     let mut word_map: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
-    let lines = get_lines_lower_case(content);
+    let mut line = String::new();
+    let mut buffers = LineBuffers::default();
+    let mut splitter = normalizer.field_splitter();
+    let mut line_number = 0;
 
-    for(line_number, line) in lines.iter().enumerate() {
-        let words: HashSet<&str> = line.split_whitespace().collect();
-        for word in words {
-            let word = word.to_string();
-            word_map.entry(word).or_insert_with(BTreeSet::new).insert(line_number + 1);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
         }
+        line_number += 1;
+        normalizer.line_terms(&mut splitter, &line, &mut buffers, |word| {
+            match word_map.get_mut(word) {
+                Some(lines) => {
+                    lines.insert(line_number);
+                }
+                None => {
+                    word_map.insert(word.to_string(), BTreeSet::from([line_number]));
+                }
+            }
+        });
     }
 
-    word_map
-}
\ No newline at end of file
+    Ok(word_map)
+}
+
+pub fn solve_file(
+    file_path: &str,
+    encoding: Option<Encoding>,
+    normalizer: &Normalizer,
+) -> io::Result<BTreeMap<String, BTreeSet<usize>>> {
+    solve_reader_with(open_file(file_path, encoding)?, normalizer)
+}
+
+pub fn open_file(file_path: &str, encoding: Option<Encoding>) -> io::Result<impl BufRead> {
+    Ok(BufReader::new(DecodeReader::new(
+        File::open(file_path)?,
+        encoding,
+    )))
+}
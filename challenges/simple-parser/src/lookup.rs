@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+// Term lookups over any sorted term map: the `solve` word map, the postings of
+// an `Index` or `Corpus::word_map`. They all walk the map in order and skip
+// whole ranges of terms that cannot match, instead of testing every term.
+
+pub fn prefix_terms<'a, V>(map: &'a BTreeMap<String, V>, prefix: &str) -> Vec<(&'a str, &'a V)> {
+    map.range::<str, _>((Included(prefix), Unbounded))
+        .take_while(|(term, _)| term.starts_with(prefix))
+        .map(|(term, value)| (term.as_str(), value))
+        .collect()
+}
+
+// Glob patterns: `*` matches any run of characters, `?` exactly one. Only
+// the terms starting with the literal part before the first wildcard are
+// looked at.
+pub fn glob_terms<'a, V>(map: &'a BTreeMap<String, V>, pattern: &str) -> Vec<(&'a str, &'a V)> {
+    let literal = pattern
+        .find(['*', '?'])
+        .map_or(pattern, |wildcard| &pattern[..wildcard]);
+    let pattern: Vec<char> = pattern.chars().collect();
+    prefix_terms(map, literal)
+        .into_iter()
+        .filter(|(term, _)| glob_matches(&pattern, &term.chars().collect::<Vec<_>>()))
+        .collect()
+}
+
+fn glob_matches(pattern: &[char], text: &[char]) -> bool {
+    // Backtracks only to the most recent `*`, which is enough for globs.
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Terms within `max_distance` edits of `word` (insertions, deletions,
+// substitutions and swaps of two neighbouring characters), with their
+// distance. The edit distance rows are computed once per shared prefix of
+// consecutive terms, like running a Levenshtein automaton over the sorted
+// terms, and as soon as a prefix can no longer lead to a match every term
+// starting with it is skipped.
+pub fn fuzzy_terms<'a, V>(
+    map: &'a BTreeMap<String, V>,
+    word: &str,
+    max_distance: usize,
+) -> Vec<(&'a str, usize, &'a V)> {
+    let word: Vec<char> = word.chars().collect();
+    let mut rows: Vec<Vec<usize>> = vec![(0..=word.len()).collect()];
+    let mut prefix: Vec<char> = Vec::new();
+    let mut matches = Vec::new();
+    let mut from: (Bound<String>, Bound<String>) = (Unbounded, Unbounded);
+
+    while let Some((term, value)) = map.range::<String, _>(from.clone()).next() {
+        let chars: Vec<char> = term.chars().collect();
+        let common = prefix
+            .iter()
+            .zip(&chars)
+            .take_while(|(a, b)| a == b)
+            .count();
+        prefix.truncate(common);
+        rows.truncate(common + 1);
+
+        let mut dead = false;
+        for &c in &chars[common..] {
+            prefix.push(c);
+            let row = next_row(&rows, &prefix, &word);
+            let best = row.iter().min().copied().unwrap_or(0);
+            let previous_best = rows
+                .last()
+                .and_then(|row| row.iter().min().copied())
+                .unwrap_or(0);
+            rows.push(row);
+            // A swap can still reach back to the row before, one edit later.
+            if best > max_distance && previous_best >= max_distance {
+                dead = true;
+                break;
+            }
+        }
+        if dead {
+            match prefix_successor(&prefix) {
+                Some(next) => from = (Included(next), Unbounded),
+                None => break,
+            }
+            continue;
+        }
+        let distance = rows[rows.len() - 1][word.len()];
+        if distance <= max_distance {
+            matches.push((term.as_str(), distance, value));
+        }
+        from = (Excluded(term.clone()), Unbounded);
+    }
+    matches
+}
+
+// The edit distance row for the last character of `prefix`.
+fn next_row(rows: &[Vec<usize>], prefix: &[char], word: &[char]) -> Vec<usize> {
+    let i = prefix.len();
+    let c = prefix[i - 1];
+    let above = &rows[i - 1];
+    let mut row = Vec::with_capacity(word.len() + 1);
+    row.push(i);
+    for j in 1..=word.len() {
+        let cost = usize::from(word[j - 1] != c);
+        let mut distance = (above[j] + 1).min(row[j - 1] + 1).min(above[j - 1] + cost);
+        if i > 1 && j > 1 && c == word[j - 2] && prefix[i - 2] == word[j - 1] {
+            distance = distance.min(rows[i - 2][j - 2] + 1);
+        }
+        row.push(distance);
+    }
+    row
+}
+
+// The smallest string that is greater than every string starting with `prefix`.
+fn prefix_successor(prefix: &[char]) -> Option<String> {
+    let mut prefix = prefix.to_vec();
+    while let Some(c) = prefix.pop() {
+        let next = (c as u32 + 1..=char::MAX as u32).find_map(char::from_u32);
+        if let Some(next) = next {
+            prefix.push(next);
+            return Some(prefix.into_iter().collect());
+        }
+    }
+    None
+}
+
+// The default fuzzy distance for a word: exact for very short words, one edit
+// up to five characters and two beyond.
+pub fn default_distance(word: &str) -> usize {
+    match word.chars().count() {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+pub fn lookup_prefix(
+    word_map: &BTreeMap<String, BTreeSet<usize>>,
+    prefix: &str,
+) -> BTreeSet<usize> {
+    merge_lines(
+        prefix_terms(word_map, prefix)
+            .into_iter()
+            .map(|(_, lines)| lines),
+    )
+}
+
+pub fn lookup_glob(word_map: &BTreeMap<String, BTreeSet<usize>>, pattern: &str) -> BTreeSet<usize> {
+    merge_lines(
+        glob_terms(word_map, pattern)
+            .into_iter()
+            .map(|(_, lines)| lines),
+    )
+}
+
+pub fn lookup_fuzzy(
+    word_map: &BTreeMap<String, BTreeSet<usize>>,
+    word: &str,
+    max_distance: usize,
+) -> BTreeSet<usize> {
+    merge_lines(
+        fuzzy_terms(word_map, word, max_distance)
+            .into_iter()
+            .map(|(_, _, lines)| lines),
+    )
+}
+
+fn merge_lines<'a>(sets: impl Iterator<Item = &'a BTreeSet<usize>>) -> BTreeSet<usize> {
+    sets.flatten().copied().collect()
+}
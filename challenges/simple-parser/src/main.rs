@@ -1,12 +1,982 @@
+use rust_coding_challenges::encoding::Encoding;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use simple_parser::{
+    column, concordance, default_distance, diff_word_maps, fuzzy_terms, glob_terms, highlight,
+    is_index_file, open_file, page, page_count, parse_query, prefix_terms, rank_corpus_lines,
+    rank_documents, save_index, solve_file, solve_file_parallel, sorted_entries,
+    sorted_entries_with, Bm25, Corpus, DocId, IncrementalIndex, Index, InputFormat, InternedIndex,
+    Language, LineRanges, MappedIndex, Normalizer, SearchServer, Session, SortOrder, TextStats,
+    SESSION_COMMANDS,
+};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
 use std::io;
-use simple_parser::solve;
-use rust_coding_challenges::utils::read_text_file_from_args;
+use std::io::{BufRead, IsTerminal};
+use std::net::TcpListener;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: simple-parser [options] [--sort byte|uca|frequency|first] [--page <n>] [--page-size <n>] \
+                     <file_or_directory>...\n       \
+                     simple-parser --interactive [options] <file_path>\n       \
+                     simple-parser --watch [--interval <ms>] [options] <file_path>\n       \
+                     simple-parser index [options] <file_path> <index_path>\n       \
+                     simple-parser lookup <index_path> <word>...\n       \
+                     simple-parser query [options] [--interned] <file_or_directory>... <query>\n       \
+                     simple-parser search [options] [--top <k>] [--documents] <file_or_directory>... <query>\n       \
+                     simple-parser prefix|glob [options] <file_or_directory>... <pattern>\n       \
+                     simple-parser fuzzy [options] [--distance <n>] <file_or_directory>... <word>\n       \
+                     simple-parser stats [options] [--top <k>] <file_or_directory>...\n       \
+                     simple-parser diff [options] [--json] <old_file_or_index> <new_file_or_index>\n       \
+                     simple-parser serve [options] [--port <n>] [--interval <ms>] <file_or_directory>...\n       \
+                     simple-parser kwic [options] [--width <n>] <file_or_directory>... <word>\n       \
+                     simple-parser snippets [options] <file_or_directory>... <query>\n\
+                     Options: [--encoding <name>] [--format text|csv|tsv|jsonl|markdown|code] [--language <name>] [--comments] [--strings] [--normalize] [--strip-punctuation] [--fold-apostrophes] \
+                     [--strip-possessives] [--split-hyphens] [--case-fold] [--stem] [--stopwords] [--stopwords-file <path>] [--threads <n>] [--max-lines <n>]";
+
+// Options every command that reads texts understands.
+const TEXT_OPTIONS: [&str; 14] = [
+    "--encoding",
+    "--format",
+    "--language",
+    "--comments",
+    "--strings",
+    "--normalize",
+    "--strip-punctuation",
+    "--fold-apostrophes",
+    "--strip-possessives",
+    "--split-hyphens",
+    "--case-fold",
+    "--stem",
+    "--stopwords",
+    "--stopwords-file",
+];
+
+struct Args {
+    positional: Vec<String>,
+    // The options given besides the text options, to reject the ones a
+    // command would ignore.
+    options: Vec<String>,
+    encoding: Option<Encoding>,
+    normalizer: Normalizer,
+    top: usize,
+    documents: bool,
+    threads: Option<usize>,
+    distance: Option<usize>,
+    width: usize,
+    interactive: bool,
+    watch: bool,
+    interval: Duration,
+    sort: SortOrder,
+    max_lines: Option<usize>,
+    page: Option<usize>,
+    page_size: usize,
+    json: bool,
+    interned: bool,
+    port: u16,
+}
+
+// Parses the arguments of a command taking `allowed` besides the text options.
+fn parse_args(args: &[String], allowed: &[&str]) -> io::Result<Args> {
+    let mut positional = Vec::new();
+    let mut options = Vec::new();
+    let mut encoding = None;
+    let mut normalizer = Normalizer::default();
+    let mut top = 10;
+    let mut documents = false;
+    let mut threads = None;
+    let mut distance = None;
+    let mut width = 30;
+    let mut interactive = false;
+    let mut watch = false;
+    let mut interval = Duration::from_millis(1000);
+    let mut sort = SortOrder::default();
+    let mut max_lines = None;
+    let mut page = None;
+    let mut page_size = 50;
+    let mut json = false;
+    let mut interned = false;
+    let mut port = 8080;
+    let mut stopwords = BTreeSet::new();
+    let (mut comments, mut strings) = (false, false);
+    let mut language = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg.starts_with("--") && !TEXT_OPTIONS.contains(&arg.as_str()) {
+            options.push(arg.clone());
+        }
+        match arg.as_str() {
+            "--encoding" => {
+                let name = iter.next().ok_or_else(usage)?;
+                encoding = Some(
+                    Encoding::from_label(name)
+                        .ok_or_else(|| invalid_input(format!("Unknown encoding: {}", name)))?,
+                );
+            }
+            "--format" => {
+                let name = iter.next().ok_or_else(usage)?;
+                normalizer.format = InputFormat::from_name(name)
+                    .ok_or_else(|| invalid_input(format!("Unknown format: {}", name)))?;
+            }
+            "--language" => {
+                let name = iter.next().ok_or_else(usage)?;
+                language = Some(
+                    Language::from_name(name)
+                        .ok_or_else(|| invalid_input(format!("Unknown language: {}", name)))?,
+                );
+            }
+            "--comments" => comments = true,
+            "--strings" => strings = true,
+            "--normalize" => {
+                normalizer.strip_punctuation = true;
+                normalizer.fold_apostrophes = true;
+                normalizer.strip_possessives = true;
+                normalizer.case_fold = true;
+            }
+            "--strip-punctuation" => normalizer.strip_punctuation = true,
+            "--fold-apostrophes" => normalizer.fold_apostrophes = true,
+            "--strip-possessives" => normalizer.strip_possessives = true,
+            "--split-hyphens" => normalizer.split_hyphens = true,
+            "--case-fold" => normalizer.case_fold = true,
+            "--stem" => normalizer.stem = true,
+            "--stopwords" => stopwords.extend(Normalizer::english_stopwords()),
+            "--stopwords-file" => {
+                stopwords.extend(Normalizer::read_stopwords(iter.next().ok_or_else(usage)?)?)
+            }
+            "--top" => top = iter.next().and_then(|k| k.parse().ok()).ok_or_else(usage)?,
+            "--documents" => documents = true,
+            "--json" => json = true,
+            "--interned" => interned = true,
+            "--port" => port = iter.next().and_then(|n| n.parse().ok()).ok_or_else(usage)?,
+            "--interactive" => interactive = true,
+            "--watch" => watch = true,
+            "--max-lines" => {
+                max_lines = Some(iter.next().and_then(|n| n.parse().ok()).ok_or_else(usage)?)
+            }
+            "--page" => {
+                page = Some(
+                    iter.next()
+                        .and_then(|n| n.parse().ok())
+                        .filter(|&n| n > 0)
+                        .ok_or_else(usage)?,
+                )
+            }
+            "--page-size" => {
+                page_size = iter
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n > 0)
+                    .ok_or_else(usage)?
+            }
+            "--sort" => {
+                let name = iter.next().ok_or_else(usage)?;
+                sort = SortOrder::from_name(name)
+                    .ok_or_else(|| invalid_input(format!("Unknown sort order: {}", name)))?;
+            }
+            "--interval" => {
+                interval = Duration::from_millis(
+                    iter.next()
+                        .and_then(|ms| ms.parse().ok())
+                        .ok_or_else(usage)?,
+                )
+            }
+            "--width" => width = iter.next().and_then(|n| n.parse().ok()).ok_or_else(usage)?,
+            "--distance" => {
+                distance = Some(iter.next().and_then(|n| n.parse().ok()).ok_or_else(usage)?)
+            }
+            "--threads" => {
+                threads = Some(iter.next().and_then(|n| n.parse().ok()).ok_or_else(usage)?)
+            }
+            _ if arg.starts_with("--") => return Err(usage()),
+            _ => positional.push(arg.clone()),
+        }
+    }
+    normalizer.stopwords = Arc::new(stopwords);
+    if let Some(option) = ignored_option(&options, allowed) {
+        return Err(invalid_input(format!(
+            "{} cannot be used with this command",
+            option
+        )));
+    }
+    if let InputFormat::Code { .. } = normalizer.format {
+        // Without `--language`, the extension of the first file decides.
+        let language = language
+            .or_else(|| {
+                positional
+                    .first()
+                    .and_then(|path| Language::from_extension(path))
+            })
+            .unwrap_or_default();
+        normalizer.format = InputFormat::Code {
+            language,
+            comments,
+            strings,
+        };
+    } else if comments || strings || language.is_some() {
+        return Err(invalid_input(
+            "--language, --comments and --strings need --format code".to_string(),
+        ));
+    }
+    Ok(Args {
+        positional,
+        options,
+        encoding,
+        normalizer,
+        top,
+        documents,
+        threads,
+        distance,
+        width,
+        interactive,
+        watch,
+        interval,
+        sort,
+        max_lines,
+        page,
+        page_size,
+        json,
+        interned,
+        port,
+    })
+}
+
+// The first given option that is not `allowed`, which would be ignored.
+fn ignored_option<'a>(options: &'a [String], allowed: &[&str]) -> Option<&'a str> {
+    options
+        .iter()
+        .map(String::as_str)
+        .find(|option| !allowed.contains(option))
+}
+
+fn usage() -> io::Error {
+    invalid_input(USAGE.to_string())
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
 fn main() -> io::Result<()> {
-    let content = read_text_file_from_args()?;
-    let word_map = solve(&content);
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("index") => build_index(parse_args(&args[2..], &["--threads"])?),
+        Some("lookup") => lookup(&args[2..]),
+        Some("query") => query(parse_args(&args[2..], &["--interned", "--max-lines"])?),
+        Some("search") => search(parse_args(&args[2..], &["--top", "--documents"])?),
+        Some("stats") => stats(parse_args(&args[2..], &["--top"])?),
+        Some("diff") => diff(parse_args(&args[2..], &["--json", "--threads"])?),
+        Some("serve") => serve(parse_args(&args[2..], &["--port", "--interval"])?),
+        Some("kwic") => kwic(parse_args(&args[2..], &["--width"])?),
+        Some("snippets") => snippets(parse_args(&args[2..], &[])?),
+        Some(kind @ ("prefix" | "glob")) => {
+            term_lookup(kind, parse_args(&args[2..], &["--max-lines", "--threads"])?)
+        }
+        Some("fuzzy") => term_lookup(
+            "fuzzy",
+            parse_args(&args[2..], &["--max-lines", "--threads", "--distance"])?,
+        ),
+        _ => print_index(parse_args(
+            &args[1..],
+            &[
+                "--sort",
+                "--page",
+                "--page-size",
+                "--max-lines",
+                "--threads",
+                "--interactive",
+                "--watch",
+                "--interval",
+            ],
+        )?),
+    }
+}
+
+// `--threads` only speeds up building the word map of a single file.
+fn single_file_threads(args: &Args) -> io::Result<()> {
+    match args.threads {
+        Some(_) => Err(invalid_input(
+            "--threads needs a single file and cannot be used with --stem or --sort first"
+                .to_string(),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn print_index(args: Args) -> io::Result<()> {
+    // The session and the watcher print words their own way.
+    let mode_options: &[&str] = if args.interactive {
+        &["--interactive"]
+    } else if args.watch {
+        &["--watch", "--interval", "--max-lines"]
+    } else {
+        &[
+            "--sort",
+            "--page",
+            "--page-size",
+            "--max-lines",
+            "--threads",
+        ]
+    };
+    if let Some(option) = ignored_option(&args.options, mode_options) {
+        return Err(invalid_input(format!(
+            "{} cannot be used {}",
+            option,
+            match (args.interactive, args.watch) {
+                (true, _) => "with --interactive",
+                (_, true) => "with --watch",
+                _ => "without --watch",
+            }
+        )));
+    }
+    match args.positional.as_slice() {
+        [] => Err(usage()),
+        [file_path] if args.interactive => interactive(file_path, &args),
+        [file_path] if args.watch => watch(file_path, &args),
+        _ if args.interactive || args.watch => Err(usage()),
+        // Surface forms need the index, and the order of words within a line
+        // its positions.
+        [file_path]
+            if !Path::new(file_path).is_dir()
+                && (args.normalizer.stem || args.sort == SortOrder::FirstAppearance) =>
+        {
+            single_file_threads(&args)?;
+            let reader = open_file(file_path, args.encoding)?;
+            let index = match args.sort {
+                SortOrder::FirstAppearance => {
+                    Index::build_with_positions(reader, &args.normalizer)?
+                }
+                _ => Index::build(reader, &args.normalizer)?,
+            };
+            let word_map = index.to_word_map();
+            let entries = sorted_entries_with(&word_map, args.sort, |word, _, line| {
+                index.first_position(word, line).unwrap_or(0)
+            });
+            for (word, lines) in paged(&entries, &args) {
+                let forms = if args.normalizer.stem {
+                    format_surface_forms(&index.surface_forms(word))
+                } else {
+                    String::new()
+                };
+                println!(
+                    "Word: '{}'{}, Lines: {}",
+                    word,
+                    forms,
+                    format_lines(lines, &args)
+                );
+            }
+            print_page_footer(entries.len(), &args);
+            Ok(())
+        }
+        [file_path] if !Path::new(file_path).is_dir() => {
+            let word_map = solve_single_file(file_path, &args)?;
+
+            let entries = sorted_entries(&word_map, args.sort);
+            for (word, lines) in paged(&entries, &args) {
+                println!("Word: '{}', Lines: {}", word, format_lines(lines, &args));
+            }
+            print_page_footer(entries.len(), &args);
+            Ok(())
+        }
+        paths => {
+            single_file_threads(&args)?;
+            let corpus = build_corpus(paths, &args, args.sort == SortOrder::FirstAppearance)?;
+            let word_map = corpus.word_map();
+            let entries = sorted_entries_with(&word_map, args.sort, |word, id, line| {
+                corpus
+                    .document(id)
+                    .and_then(|document| document.index.first_position(word, line))
+                    .unwrap_or(0)
+            });
+            for (word, postings) in paged(&entries, &args) {
+                let forms = if args.normalizer.stem {
+                    format_surface_forms(&corpus.surface_forms(word))
+                } else {
+                    String::new()
+                };
+                println!(
+                    "Word: '{}'{}, {}",
+                    word,
+                    forms,
+                    format_postings(&corpus, postings, args.max_lines)
+                );
+            }
+            print_page_footer(entries.len(), &args);
+            Ok(())
+        }
+    }
+}
+
+fn solve_single_file(
+    file_path: &str,
+    args: &Args,
+) -> io::Result<BTreeMap<String, BTreeSet<usize>>> {
+    match args.threads {
+        Some(threads) => solve_file_parallel(file_path, args.encoding, &args.normalizer, threads),
+        None => solve_file(file_path, args.encoding, &args.normalizer),
+    }
+}
+
+// The words a stem was found as, e.g. " [programming, programs]".
+fn format_surface_forms(forms: &BTreeSet<String>) -> String {
+    format!(
+        " [{}]",
+        forms
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+// Positions are only kept for the commands that rank, match phrases or
+// show where words are.
+fn build_corpus(paths: &[String], args: &Args, has_positions: bool) -> io::Result<Corpus> {
+    let mut corpus = match has_positions {
+        true => Corpus::with_positions(args.normalizer.clone()),
+        false => Corpus::new(args.normalizer.clone()),
+    };
+    for path in paths {
+        corpus.add_path(path, args.encoding)?;
+    }
+    Ok(corpus)
+}
+
+fn format_postings(
+    corpus: &Corpus,
+    postings: &BTreeMap<DocId, BTreeSet<usize>>,
+    max_lines: Option<usize>,
+) -> String {
+    postings
+        .iter()
+        .map(|(&id, lines)| {
+            let name = &corpus.document(id).unwrap().name;
+            format!("{}: {}", name, LineRanges::new(lines).max_lines(max_lines))
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn format_lines<'a>(lines: &'a BTreeSet<usize>, args: &Args) -> LineRanges<'a> {
+    LineRanges::new(lines).max_lines(args.max_lines)
+}
+
+// Without `--page` the whole listing is printed.
+fn paged<'a, T>(items: &'a [T], args: &Args) -> &'a [T] {
+    match args.page {
+        Some(number) => page(items, number, args.page_size),
+        None => items,
+    }
+}
+
+fn print_page_footer(items: usize, args: &Args) {
+    if let Some(number) = args.page {
+        let first = ((number.max(1) - 1) * args.page_size + 1).min(items);
+        let last = (number.max(1) * args.page_size).min(items);
+        println!(
+            "Page {} of {}, words {}-{} of {}",
+            number,
+            page_count(items, args.page_size),
+            first,
+            last,
+            items
+        );
+    }
+}
+
+fn build_index(args: Args) -> io::Result<()> {
+    let [file_path, index_path] = args.positional.as_slice() else {
+        return Err(usage());
+    };
+    let word_map = solve_single_file(file_path, &args)?;
+    save_index(&word_map, &args.normalizer, index_path)?;
+    println!(
+        "Indexed {} words from '{}' into '{}'",
+        word_map.len(),
+        file_path,
+        index_path
+    );
+    Ok(())
+}
+
+fn lookup(args: &[String]) -> io::Result<()> {
+    let [index_path, words @ ..] = args else {
+        return Err(usage());
+    };
+    let index = MappedIndex::open(index_path)?;
+    for word in words {
+        for term in index.normalizer().normalize_word(word) {
+            match index.get(&term) {
+                Some(lines) => println!("Word: '{}', Lines: {}", term, LineRanges::new(&lines)),
+                None => println!("Word: '{}' not found", term),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn query(args: Args) -> io::Result<()> {
+    let [paths @ .., query] = args.positional.as_slice() else {
+        return Err(usage());
+    };
+    match paths {
+        [] => Err(usage()),
+        [file_path] if !Path::new(file_path).is_dir() => {
+            let query = parse_query(query, &args.normalizer).map_err(invalid_input)?;
+            let lines = if args.interned {
+                query.evaluate(&InternedIndex::build(
+                    open_file(file_path, args.encoding)?,
+                    &args.normalizer,
+                )?)
+            } else {
+                query.evaluate(&Index::build_with_positions(
+                    open_file(file_path, args.encoding)?,
+                    &args.normalizer,
+                )?)
+            };
+
+            println!("Lines: {}", format_lines(&lines, &args));
+            print_lines(file_path, args.encoding, &lines, "")
+        }
+        paths => {
+            if args.interned {
+                return Err(invalid_input("--interned needs a single file".to_string()));
+            }
+            let corpus = build_corpus(paths, &args, true)?;
+            let query = parse_query(query, corpus.normalizer()).map_err(invalid_input)?;
+            let matches = corpus.query(&query);
+
+            println!("{}", format_postings(&corpus, &matches, args.max_lines));
+            for (id, lines) in matches {
+                let name = &corpus.document(id).unwrap().name;
+                print_lines(name, args.encoding, &lines, &format!("{}:", name))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn print_lines(
+    file_path: &str,
+    encoding: Option<Encoding>,
+    lines: &BTreeSet<usize>,
+    prefix: &str,
+) -> io::Result<()> {
+    for (line_number, line) in open_file(file_path, encoding)?.lines().enumerate() {
+        let line = line?;
+        if lines.contains(&(line_number + 1)) {
+            println!("{}{:>6}: {}", prefix, line_number + 1, line);
+        }
+    }
+    Ok(())
+}
+
+fn search(args: Args) -> io::Result<()> {
+    let [paths @ .., query] = args.positional.as_slice() else {
+        return Err(usage());
+    };
+    if paths.is_empty() {
+        return Err(usage());
+    }
+    let corpus = build_corpus(paths, &args, true)?;
+    let params = Bm25::default();
+    if args.documents {
+        for hit in rank_documents(&corpus, query, args.top, &params) {
+            println!(
+                "{:>8.3}  {}",
+                hit.score,
+                corpus.document(hit.document).unwrap().name
+            );
+        }
+        return Ok(());
+    }
+
+    let hits = rank_corpus_lines(&corpus, query, args.top, &params);
+    let mut wanted: BTreeMap<DocId, BTreeSet<usize>> = BTreeMap::new();
+    for hit in &hits {
+        wanted.entry(hit.document).or_default().insert(hit.line);
+    }
+    let mut texts = BTreeMap::new();
+    for (id, lines) in wanted {
+        let name = &corpus.document(id).unwrap().name;
+        for (line_number, line) in open_file(name, args.encoding)?.lines().enumerate() {
+            if lines.contains(&(line_number + 1)) {
+                texts.insert((id, line_number + 1), line?);
+            }
+        }
+    }
+    for hit in hits {
+        let name = &corpus.document(hit.document).unwrap().name;
+        let text = texts
+            .get(&(hit.document, hit.line))
+            .map_or("", String::as_str);
+        println!("{:>8.3}  {}:{}: {}", hit.score, name, hit.line, text);
+    }
+    Ok(())
+}
+
+// Prints every matching term with its lines, then the merged lines. The
+// pattern is lower-cased like the indexed words; fuzzy matches also show
+// their edit distance.
+fn term_lookup(kind: &str, args: Args) -> io::Result<()> {
+    let [paths @ .., pattern] = args.positional.as_slice() else {
+        return Err(usage());
+    };
+    let pattern = args.normalizer.normalize_pattern(pattern);
+    let distance = args.distance.unwrap_or_else(|| default_distance(&pattern));
+    match paths {
+        [] => Err(usage()),
+        [file_path] if !Path::new(file_path).is_dir() => {
+            let word_map = solve_single_file(file_path, &args)?;
+            let mut merged: BTreeSet<usize> = BTreeSet::new();
+            for (term, distance, lines) in match_terms(kind, &word_map, &pattern, distance) {
+                println!(
+                    "Word: '{}'{}, Lines: {}",
+                    term,
+                    format_distance(kind, distance),
+                    format_lines(lines, &args)
+                );
+                merged.extend(lines);
+            }
+            println!("Lines: {}", format_lines(&merged, &args));
+            Ok(())
+        }
+        paths => {
+            single_file_threads(&args)?;
+            let corpus = build_corpus(paths, &args, false)?;
+            let word_map = corpus.word_map();
+            let mut merged: BTreeMap<DocId, BTreeSet<usize>> = BTreeMap::new();
+            for (term, distance, postings) in match_terms(kind, &word_map, &pattern, distance) {
+                println!(
+                    "Word: '{}'{}, {}",
+                    term,
+                    format_distance(kind, distance),
+                    format_postings(&corpus, postings, args.max_lines)
+                );
+                for (&id, lines) in postings {
+                    merged.entry(id).or_default().extend(lines);
+                }
+            }
+            println!("{}", format_postings(&corpus, &merged, args.max_lines));
+            Ok(())
+        }
+    }
+}
 
-    for (word, lines) in word_map {
-        println!("Word: '{}', Lines: {:?}", word, lines);
+fn match_terms<'a, V>(
+    kind: &str,
+    map: &'a BTreeMap<String, V>,
+    pattern: &str,
+    distance: usize,
+) -> Vec<(&'a str, usize, &'a V)> {
+    match kind {
+        "prefix" => prefix_terms(map, pattern)
+            .into_iter()
+            .map(|(term, value)| (term, 0, value))
+            .collect(),
+        "glob" => glob_terms(map, pattern)
+            .into_iter()
+            .map(|(term, value)| (term, 0, value))
+            .collect(),
+        _ => fuzzy_terms(map, pattern, distance),
+    }
+}
+
+fn format_distance(kind: &str, distance: usize) -> String {
+    if kind == "fuzzy" {
+        format!(" (distance {})", distance)
+    } else {
+        String::new()
+    }
+}
+
+fn read_lines(file_path: &str, encoding: Option<Encoding>) -> io::Result<Vec<String>> {
+    open_file(file_path, encoding)?.lines().collect()
+}
+
+// Prefixes output lines with the document name when there are several.
+fn document_prefix(corpus: &Corpus, name: &str) -> String {
+    if corpus.len() > 1 {
+        format!("{}:", name)
+    } else {
+        String::new()
+    }
+}
+
+fn kwic(args: Args) -> io::Result<()> {
+    let [paths @ .., word] = args.positional.as_slice() else {
+        return Err(usage());
+    };
+    if paths.is_empty() {
+        return Err(usage());
+    }
+    let corpus = build_corpus(paths, &args, true)?;
+    let terms = corpus.normalizer().normalize_word(word);
+    for (_, document) in corpus.documents() {
+        let lines = read_lines(&document.name, args.encoding)?;
+        let prefix = document_prefix(&corpus, &document.name);
+        for term in &terms {
+            for kwic_line in concordance(&document.index, &lines, term, args.width) {
+                println!("{}{}", prefix, kwic_line);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Matching lines of a query with the query terms highlighted when writing to
+// a terminal, each with the column of its first match.
+fn snippets(args: Args) -> io::Result<()> {
+    let [paths @ .., query] = args.positional.as_slice() else {
+        return Err(usage());
+    };
+    if paths.is_empty() {
+        return Err(usage());
+    }
+    let corpus = build_corpus(paths, &args, true)?;
+    let query = parse_query(query, corpus.normalizer()).map_err(invalid_input)?;
+    let terms = query.terms();
+    let color = io::stdout().is_terminal();
+    for (id, matches) in corpus.query(&query) {
+        let document = corpus.document(id).unwrap();
+        let lines = read_lines(&document.name, args.encoding)?;
+        let prefix = document_prefix(&corpus, &document.name);
+        for line in matches {
+            // The file may have changed since it was indexed.
+            let Some(text) = lines.get(line - 1) else {
+                continue;
+            };
+            let mut spans: Vec<(usize, usize)> = terms
+                .iter()
+                .flat_map(|term| document.index.occurrences(term))
+                .filter(|occurrence| occurrence.line == line)
+                .map(|occurrence| (occurrence.start, occurrence.end))
+                .filter(|&(start, end)| text.get(start..end).is_some())
+                .collect();
+            spans.sort();
+            let first = spans.first().map_or(1, |&(start, _)| column(text, start));
+            let text = if color {
+                highlight(text, &spans)
+            } else {
+                text.clone()
+            };
+            println!("{}{}:{}: {}", prefix, line, first, text);
+        }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+// Completes command names at the start of the line and indexed words after.
+struct SessionHelper {
+    session: Rc<RefCell<Session>>,
+}
+
+impl Completer for SessionHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = line[start..pos].to_lowercase();
+        let candidates = if start == 0 {
+            SESSION_COMMANDS
+                .iter()
+                .filter(|command| command.starts_with(&word))
+                .map(|command| command.to_string())
+                .collect()
+        } else {
+            self.session
+                .borrow()
+                .complete(&word)
+                .into_iter()
+                .map(str::to_string)
+                .collect()
+        };
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for SessionHelper {
+    type Hint = String;
+}
+
+impl Highlighter for SessionHelper {}
+
+impl Validator for SessionHelper {}
+
+impl Helper for SessionHelper {}
+
+fn history_path() -> Option<std::path::PathBuf> {
+    env::var_os("HOME").map(|home| Path::new(&home).join(".simple_parser_history"))
+}
+
+fn interactive(file_path: &str, args: &Args) -> io::Result<()> {
+    let session = Rc::new(RefCell::new(Session::open(
+        file_path,
+        args.encoding,
+        args.normalizer.clone(),
+    )?));
+    println!(
+        "{}",
+        session.borrow_mut().execute("stats").unwrap_or_default()
+    );
+    println!("Type 'help' for a list of commands.");
+
+    let mut editor: Editor<SessionHelper, DefaultHistory> =
+        Editor::new().map_err(io::Error::other)?;
+    editor.set_helper(Some(SessionHelper {
+        session: Rc::clone(&session),
+    }));
+    let history = history_path();
+    if let Some(history) = &history {
+        // There is no history yet on the first run.
+        let _ = editor.load_history(history);
+    }
+    loop {
+        match editor.readline("simple-parser> ") {
+            Ok(line) => {
+                let command = line.trim();
+                if command.is_empty() {
+                    continue;
+                }
+                let _ = editor.add_history_entry(command);
+                if command == "quit" || command == "exit" {
+                    break;
+                }
+                match session.borrow_mut().execute(command) {
+                    Ok(output) => println!("{}", output),
+                    Err(err) => eprintln!("Error: {}", err),
+                }
+            }
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(err) => return Err(io::Error::other(err)),
+        }
+    }
+    if let Some(history) = &history {
+        if let Err(err) = editor.save_history(history) {
+            eprintln!("Cannot save history to '{}': {}", history.display(), err);
+        }
+    }
+    Ok(())
+}
+
+// Prints the index, then polls the file and after every change prints which
+// lines changed and the new lines of the words in them.
+fn watch(file_path: &str, args: &Args) -> io::Result<()> {
+    let mut index = IncrementalIndex::new(args.normalizer.clone());
+    index.update_file(file_path, args.encoding)?;
+    for (word, lines) in index.word_map() {
+        println!("Word: '{}', Lines: {}", word, format_lines(lines, args));
+    }
+    let stamp = |metadata: fs::Metadata| (metadata.len(), metadata.modified().ok());
+    let mut last = stamp(fs::metadata(file_path)?);
+    loop {
+        thread::sleep(args.interval);
+        // A missing file, e.g. while a log is rotated or an editor replaces
+        // the file by renaming, has not changed yet.
+        let current = match fs::metadata(file_path) {
+            Ok(metadata) => stamp(metadata),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if current == last {
+            continue;
+        }
+        let change = match index.update_file(file_path, args.encoding) {
+            Ok(change) => change,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        last = current;
+        if change.is_empty() {
+            continue;
+        }
+        let last_removed = change.first_line + change.removed - 1;
+        match (change.removed, change.inserted) {
+            (0, inserted) => println!(
+                "{} line(s) inserted at line {}",
+                inserted, change.first_line
+            ),
+            (_, 0) => println!("Lines {}-{} removed", change.first_line, last_removed),
+            (_, inserted) => println!(
+                "Lines {}-{} replaced by {} line(s)",
+                change.first_line, last_removed, inserted
+            ),
+        }
+        for word in &change.terms {
+            match index.word_map().get(word) {
+                Some(lines) => println!("Word: '{}', Lines: {}", word, format_lines(lines, args)),
+                None => println!("Word: '{}' removed", word),
+            }
+        }
+    }
+}
+
+fn stats(args: Args) -> io::Result<()> {
+    let stats = match args.positional.as_slice() {
+        [] => return Err(usage()),
+        [file_path] if !Path::new(file_path).is_dir() => {
+            let index = Index::build_with_positions(
+                open_file(file_path, args.encoding)?,
+                &args.normalizer,
+            )?;
+            TextStats::from_index(&index, args.top)
+        }
+        paths => TextStats::from_corpus(&build_corpus(paths, &args, true)?, args.top),
+    };
+    print!("{}", stats.render());
+    Ok(())
+}
+
+fn diff(args: Args) -> io::Result<()> {
+    let [old_path, new_path] = args.positional.as_slice() else {
+        return Err(usage());
+    };
+    let diff = diff_word_maps(&diff_input(old_path, &args)?, &diff_input(new_path, &args)?);
+    if args.json {
+        println!("{}", diff.to_json());
+    } else {
+        print!("{}", diff.render());
+    }
+    Ok(())
+}
+
+// Only listens on the loopback interface: the API is meant for local tools.
+fn serve(args: Args) -> io::Result<()> {
+    if args.positional.is_empty() {
+        return Err(usage());
+    }
+    let mut server = SearchServer::open(&args.positional, args.encoding, args.normalizer.clone())?;
+    server.reload_interval = args.interval;
+    let listener = TcpListener::bind(("127.0.0.1", args.port))?;
+    println!(
+        "Serving {} document(s) on http://{}/ (/search?q=, /word/<word>, /stats)",
+        server.document_count(),
+        listener.local_addr()?
+    );
+    server.serve(listener)
+}
+
+// A saved index is compared as it was built; the normalization options only
+// apply to texts.
+fn diff_input(path: &str, args: &Args) -> io::Result<BTreeMap<String, BTreeSet<usize>>> {
+    if is_index_file(path) {
+        Ok(MappedIndex::open(path)?.to_word_map())
+    } else {
+        solve_single_file(path, args)
+    }
+}
@@ -0,0 +1,405 @@
+use rust_stemmers::{Algorithm, Stemmer};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+
+use crate::{FieldSplitter, InputFormat};
+
+// Characters treated as an apostrophe when `fold_apostrophes` is set.
+const APOSTROPHES: [char; 5] = ['\u{2019}', '\u{2018}', '\u{02bc}', '\u{2032}', '\u{ff07}'];
+const HYPHENS: [char; 3] = ['-', '\u{2010}', '\u{2011}'];
+
+// Shared by every normalizer that stems, so none has to create its own.
+static ENGLISH_STEMMER: LazyLock<Stemmer> = LazyLock::new(|| Stemmer::create(Algorithm::English));
+
+#[rustfmt::skip]
+pub const ENGLISH_STOPWORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "am", "an", "and", "any", "are",
+    "as", "at", "be", "because", "been", "before", "being", "below", "between", "both", "but", "by",
+    "can", "could", "did", "do", "does", "doing", "don't", "down", "during", "each", "few", "for",
+    "from", "further", "had", "has", "have", "having", "he", "her", "here", "hers", "herself",
+    "him", "himself", "his", "how", "i", "if", "in", "into", "is", "it", "it's", "its", "itself",
+    "just", "me", "more", "most", "my", "myself", "no", "nor", "not", "now", "of", "off", "on",
+    "once", "only", "or", "other", "our", "ours", "ourselves", "out", "over", "own", "same", "she",
+    "should", "so", "some", "such", "than", "that", "the", "their", "theirs", "them", "themselves",
+    "then", "there", "these", "they", "this", "those", "through", "to", "too", "under", "until",
+    "up", "very", "was", "we", "were", "what", "when", "where", "which", "while", "who", "whom",
+    "why", "will", "with", "would", "you", "your", "yours", "yourself", "yourselves",
+];
+
+// A term of a line: `start..end` is the byte range of the word in the
+// original line, `surface` the normalized word before stemming.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub start: usize,
+    pub end: usize,
+    pub surface: String,
+    pub term: String,
+}
+
+// The scratch space of `line_terms`, kept by the caller for a whole input so
+// no line allocates a buffer of its own.
+#[derive(Debug, Default)]
+pub struct LineBuffers {
+    normalized: String,
+    qualified: String,
+}
+
+// Turns the whitespace separated words of a line into index terms. The
+// default only lowercases, which is what `solve` has always done.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Normalizer {
+    // "crash," -> "crash", "(rust)" -> "rust"; inner punctuation is kept.
+    pub strip_punctuation: bool,
+    // "Rust’s" and "Rust's" become the same term.
+    pub fold_apostrophes: bool,
+    // "rust's" -> "rust", so a search for a name finds its possessive too.
+    pub strip_possessives: bool,
+    // "compile-time" -> "compile", "time".
+    pub split_hyphens: bool,
+    // Unicode case folding ("Straße" -> "strasse") instead of `to_lowercase`.
+    pub case_fold: bool,
+    // English Snowball stemming: "programs", "programming" -> "program".
+    pub stem: bool,
+    // Words left out of the index, compared before stemming. Shared, so
+    // cloning a normalizer for every document stays cheap.
+    pub stopwords: Arc<BTreeSet<String>>,
+    // How lines are cut into fields before their words are normalized.
+    pub format: InputFormat,
+}
+
+impl Normalizer {
+    // Everything except hyphen splitting, which changes what counts as a word.
+    pub fn standard() -> Self {
+        Normalizer {
+            strip_punctuation: true,
+            fold_apostrophes: true,
+            strip_possessives: true,
+            split_hyphens: false,
+            case_fold: true,
+            ..Normalizer::default()
+        }
+    }
+
+    pub fn english_stopwords() -> BTreeSet<String> {
+        ENGLISH_STOPWORDS
+            .iter()
+            .map(|word| word.to_string())
+            .collect()
+    }
+
+    // Reads a stopword file: one word per line, blank lines and lines
+    // starting with `#` are ignored. Words are lowercased like the text.
+    pub fn read_stopwords<P: AsRef<Path>>(path: P) -> io::Result<BTreeSet<String>> {
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect())
+    }
+
+    // Applies the per-character steps to a whole line, writing the result into
+    // a buffer the caller can reuse between lines.
+    pub fn normalize_line(&self, line: &str, normalized: &mut String) {
+        normalized.clear();
+        if self.case_fold {
+            normalized.push_str(&caseless::default_case_fold_str(line));
+        } else if line.is_ascii() {
+            normalized.push_str(line);
+            normalized.make_ascii_lowercase();
+        } else {
+            normalized.push_str(&line.to_lowercase());
+        }
+        if self.fold_apostrophes && normalized.contains(APOSTROPHES) {
+            *normalized = normalized.replace(APOSTROPHES, "'");
+        }
+    }
+
+    // Splits a line produced by `normalize_line` into terms.
+    pub fn terms<'a>(&'a self, normalized: &'a str) -> impl Iterator<Item = Cow<'a, str>> + 'a {
+        let stemmer = self.stemmer();
+        self.words(normalized)
+            .filter_map(move |word| self.term(stemmer, word))
+    }
+
+    // A normalized word as a term: none for a stopword, stemmed if wanted.
+    fn term<'a>(&self, stemmer: Option<&Stemmer>, word: &'a str) -> Option<Cow<'a, str>> {
+        if self.stopwords.contains(word) {
+            return None;
+        }
+        Some(match stemmer {
+            Some(stemmer) => stemmer.stem(word),
+            None => Cow::Borrowed(word),
+        })
+    }
+
+    // A splitter for the lines of one input in this normalizer's format.
+    pub fn field_splitter(&self) -> FieldSplitter {
+        FieldSplitter::new(self.format)
+    }
+
+    // Calls `f` with every term of the next line of an input, in order.
+    pub fn line_terms(
+        &self,
+        splitter: &mut FieldSplitter,
+        line: &str,
+        buffers: &mut LineBuffers,
+        mut f: impl FnMut(&str),
+    ) {
+        let LineBuffers {
+            normalized,
+            qualified,
+        } = buffers;
+        self.each_line_token(
+            splitter,
+            line,
+            normalized,
+            false,
+            |_, _, prefix, _, term| {
+                if prefix.is_empty() {
+                    f(term);
+                } else {
+                    qualified.clear();
+                    qualified.push_str(prefix);
+                    qualified.push_str(term);
+                    f(qualified);
+                }
+            },
+        );
+    }
+
+    // `line_terms` with where every term is in the line. The qualified terms
+    // of named fields share the byte range of the word they were made from.
+    pub fn line_tokens(&self, splitter: &mut FieldSplitter, line: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        self.each_line_token(
+            splitter,
+            line,
+            &mut String::new(),
+            true,
+            |start, end, prefix, surface, term| {
+                tokens.push(Token {
+                    start,
+                    end,
+                    surface: format!("{}{}", prefix, surface),
+                    term: format!("{}{}", prefix, term),
+                })
+            },
+        );
+        tokens
+    }
+
+    // The tokenizer behind `line_terms` and `line_tokens`: calls `f` with the
+    // byte range, the field prefix, the normalized word and the term of every
+    // term of a line. The words of a named field are given again with the
+    // prefix `name:` once the plain terms of the field are done, so phrases
+    // neither cross fields nor mix the two kinds of terms; the prefix is empty
+    // for plain terms.
+    fn each_line_token(
+        &self,
+        splitter: &mut FieldSplitter,
+        line: &str,
+        normalized: &mut String,
+        spans: bool,
+        mut f: impl FnMut(usize, usize, &str, &str, &str),
+    ) {
+        for field in splitter.split(line) {
+            let text = &line[field.start..field.end];
+            if field.plain {
+                self.each_token(text, normalized, spans, |start, end, surface, term| {
+                    f(field.start + start, field.start + end, "", surface, term)
+                });
+            }
+            if let Some(name) = &field.name {
+                let prefix = self.field_prefix(name);
+                self.each_token(text, normalized, spans, |start, end, surface, term| {
+                    f(
+                        field.start + start,
+                        field.start + end,
+                        &prefix,
+                        surface,
+                        term,
+                    )
+                });
+            }
+        }
+    }
+
+    // "First_Name" -> "first_name:", normalized like the words so a query
+    // for `First_Name:Ada` finds it.
+    fn field_prefix(&self, name: &str) -> String {
+        let mut prefix = String::new();
+        self.normalize_line(name, &mut prefix);
+        prefix.push(':');
+        prefix
+    }
+
+    // The terms of a raw line together with where they are in it.
+    pub fn tokens(&self, line: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        self.each_token(
+            line,
+            &mut String::new(),
+            true,
+            |start, end, surface, term| {
+                tokens.push(Token {
+                    start,
+                    end,
+                    surface: surface.to_string(),
+                    term: term.to_string(),
+                })
+            },
+        );
+        tokens
+    }
+
+    // Calls `f` with the byte range, the normalized word and the term of
+    // every term of `text`. Each whitespace separated word is normalized whole
+    // and then split and trimmed by `words`, like `terms` does with a
+    // normalized line; normalizing never adds or removes whitespace, so both
+    // give the same terms. Trimming has to come second: "İ" folds to "i" and
+    // a combining dot, which is punctuation. Without `spans` the terms of a
+    // non-ASCII word get the range of the whole word, which saves mapping
+    // every character back.
+    fn each_token(
+        &self,
+        text: &str,
+        normalized: &mut String,
+        spans: bool,
+        mut f: impl FnMut(usize, usize, &str, &str),
+    ) {
+        let stemmer = self.stemmer();
+        let mut origins = Vec::new();
+        for word in text.split_whitespace() {
+            let offset = word.as_ptr() as usize - text.as_ptr() as usize;
+            self.normalize_line(word, normalized);
+            // Lowercasing ASCII keeps every byte where it is.
+            let ascii = word.is_ascii();
+            if !ascii && spans {
+                self.char_origins(word, &mut origins);
+            }
+            for piece in self.words(normalized) {
+                let Some(term) = self.term(stemmer, piece) else {
+                    continue;
+                };
+                let start = piece.as_ptr() as usize - normalized.as_ptr() as usize;
+                let end = start + piece.len();
+                let (start, end) = match (ascii, spans) {
+                    (true, _) => (start, end),
+                    (false, false) => (0, word.len()),
+                    (false, true) => (
+                        origins.get(start).map_or(0, |&(start, _)| start),
+                        origins.get(end - 1).map_or(word.len(), |&(_, end)| end),
+                    ),
+                };
+                f(offset + start, offset + end, piece, &term);
+            }
+        }
+    }
+
+    // For every byte of the normalized `word`, the byte range in `word` of
+    // the character it was made from.
+    fn char_origins(&self, word: &str, origins: &mut Vec<(usize, usize)>) {
+        origins.clear();
+        let mut normalized = String::new();
+        for (start, c) in word.char_indices() {
+            let end = start + c.len_utf8();
+            self.normalize_line(&word[start..end], &mut normalized);
+            origins.extend(std::iter::repeat_n((start, end), normalized.len()));
+        }
+    }
+
+    fn stemmer(&self) -> Option<&'static Stemmer> {
+        self.stem.then(|| &*ENGLISH_STEMMER)
+    }
+
+    fn words<'a>(&'a self, normalized: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        normalized
+            .split_whitespace()
+            .flat_map(move |word| {
+                let separators: &[char] = if self.split_hyphens { &HYPHENS } else { &[] };
+                word.split(separators)
+            })
+            .map(move |term| {
+                if self.strip_punctuation {
+                    term.trim_matches(|c: char| !c.is_alphanumeric())
+                } else {
+                    term
+                }
+            })
+            .map(move |term| self.strip_possessive(term))
+            .filter(|term| !term.is_empty())
+    }
+
+    fn strip_possessive<'a>(&self, term: &'a str) -> &'a str {
+        match self.strip_possessives {
+            true => term
+                .strip_suffix("'s")
+                .filter(|stem| !stem.is_empty())
+                .unwrap_or(term),
+            false => term,
+        }
+    }
+
+    // Normalizes a prefix, glob pattern or misspelt word for looking up terms:
+    // the per-character steps and punctuation trimming as for the text, but
+    // the `*` and `?` of globs are kept. Without wildcards the pattern is
+    // stemmed like a word, since with stemming only stems are in the index.
+    pub fn normalize_pattern(&self, pattern: &str) -> String {
+        let mut normalized = String::new();
+        self.normalize_line(pattern.trim(), &mut normalized);
+        let trimmed = match self.strip_punctuation {
+            true => normalized.trim_matches(|c: char| !c.is_alphanumeric() && c != '*' && c != '?'),
+            false => &normalized,
+        };
+        let trimmed = match trimmed.contains(['*', '?']) {
+            true => trimmed,
+            false => self.strip_possessive(trimmed),
+        };
+        match self.stemmer() {
+            Some(stemmer) if !trimmed.contains(['*', '?']) => stemmer.stem(trimmed).into_owned(),
+            _ => trimmed.to_string(),
+        }
+    }
+
+    // Normalizes a single word, e.g. a search term, the same way as the text.
+    // In the CSV, JSON and code formats `name:word` is a word of a named field.
+    pub fn normalize_word(&self, word: &str) -> Vec<String> {
+        let named_fields = matches!(
+            self.format,
+            InputFormat::Csv | InputFormat::Tsv | InputFormat::JsonLines | InputFormat::Code { .. }
+        );
+        if named_fields && word.contains(':') {
+            let plain = Normalizer {
+                format: InputFormat::Text,
+                ..self.clone()
+            };
+            return word
+                .split_whitespace()
+                .flat_map(|word| {
+                    match word
+                        .split_once(':')
+                        .filter(|(name, word)| !name.is_empty() && !word.is_empty())
+                    {
+                        Some((name, word)) => {
+                            let prefix = self.field_prefix(name);
+                            plain
+                                .normalize_word(word)
+                                .into_iter()
+                                .map(|term| format!("{}{}", prefix, term))
+                                .collect()
+                        }
+                        None => plain.normalize_word(word),
+                    }
+                })
+                .collect();
+        }
+        let mut normalized = String::new();
+        self.normalize_line(word, &mut normalized);
+        self.terms(&normalized).map(Cow::into_owned).collect()
+    }
+}
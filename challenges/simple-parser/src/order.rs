@@ -0,0 +1,97 @@
+use feruca::Collator;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::DocId;
+
+// Orders in which the words of an index can be listed. The index itself stays
+// in byte order; the other orders are computed from it when listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    // `String` order, i.e. by Unicode code point: "Zebra" < "apple" < "épée".
+    #[default]
+    Byte,
+    // Unicode Collation Algorithm with the CLDR root order: "apple" < "épée" < "Zebra".
+    Collation,
+    // Most widespread words first, by the number of lines they appear in.
+    Frequency,
+    // In the order the words first appear in the text.
+    FirstAppearance,
+}
+
+impl SortOrder {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "byte" => Some(SortOrder::Byte),
+            "uca" | "collation" => Some(SortOrder::Collation),
+            "frequency" => Some(SortOrder::Frequency),
+            "first" | "appearance" => Some(SortOrder::FirstAppearance),
+            _ => None,
+        }
+    }
+}
+
+// What the frequency and first appearance orders need from the postings of a
+// word: plain line sets, or lines grouped by document.
+pub trait Postings {
+    fn line_count(&self) -> usize;
+    // (document, line) of the first occurrence.
+    fn first_appearance(&self) -> (DocId, usize);
+}
+
+impl Postings for BTreeSet<usize> {
+    fn line_count(&self) -> usize {
+        self.len()
+    }
+
+    fn first_appearance(&self) -> (DocId, usize) {
+        (0, self.first().copied().unwrap_or(0))
+    }
+}
+
+impl Postings for BTreeMap<DocId, BTreeSet<usize>> {
+    fn line_count(&self) -> usize {
+        self.values().map(BTreeSet::len).sum()
+    }
+
+    fn first_appearance(&self) -> (DocId, usize) {
+        self.iter()
+            .find_map(|(&id, lines)| lines.first().map(|&line| (id, line)))
+            .unwrap_or_default()
+    }
+}
+
+// The entries of a word map in the given order. Ties in the frequency and
+// first appearance orders are broken by byte order. A word map has no
+// positions, so words that first appear on the same line are ties here.
+pub fn sorted_entries<V: Postings>(map: &BTreeMap<String, V>, order: SortOrder) -> Vec<(&str, &V)> {
+    sorted_entries_with(map, order, |_, _, _| 0)
+}
+
+// `sorted_entries` where `position(word, document, line)` is the position of
+// the first occurrence of a word in its first line, so the first appearance
+// order follows the words within a line too.
+pub fn sorted_entries_with<V: Postings>(
+    map: &BTreeMap<String, V>,
+    order: SortOrder,
+    position: impl Fn(&str, DocId, usize) -> usize,
+) -> Vec<(&str, &V)> {
+    let mut entries: Vec<(&str, &V)> = map
+        .iter()
+        .map(|(word, postings)| (word.as_str(), postings))
+        .collect();
+    match order {
+        SortOrder::Byte => {}
+        SortOrder::Collation => {
+            let mut collator = Collator::default();
+            entries.sort_by(|(a, _), (b, _)| collator.collate(*a, *b));
+        }
+        SortOrder::Frequency => {
+            entries.sort_by_key(|(_, postings)| std::cmp::Reverse(postings.line_count()))
+        }
+        SortOrder::FirstAppearance => entries.sort_by_cached_key(|(word, postings)| {
+            let (id, line) = postings.first_appearance();
+            (id, line, position(word, id, line))
+        }),
+    }
+    entries
+}
@@ -0,0 +1,126 @@
+use memmap2::Mmap;
+use rust_coding_challenges::encoding::{detect_bom, Encoding};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io;
+use std::thread;
+
+use crate::{read_file_with_encoding, solve_with, Normalizer};
+
+// Same result as `solve`, built on all available cores.
+pub fn solve_parallel(content: &str) -> BTreeMap<String, BTreeSet<usize>> {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    solve_parallel_with(content, &Normalizer::default(), threads)
+}
+
+// Splits `content` into `threads` chunks that end on line boundaries, indexes
+// each chunk on its own thread with line numbers starting at 1, and merges
+// neighbouring partial maps pairwise, shifting the lines of the later one by
+// the number of lines before it. Merges of different pairs run on their own
+// threads too, so no single thread merges every chunk. The result is
+// identical to `solve_with`. Formats whose lines depend on earlier ones, like
+// a CSV header, are indexed on one thread.
+pub fn solve_parallel_with(
+    content: &str,
+    normalizer: &Normalizer,
+    threads: usize,
+) -> BTreeMap<String, BTreeSet<usize>> {
+    let chunks = line_chunks(content, threads.max(1));
+    if chunks.len() <= 1 || normalizer.format.is_stateful() {
+        return solve_with(content, normalizer);
+    }
+    solve_chunks(&chunks, normalizer).0
+}
+
+// The word map and line count of consecutive chunks: both halves are solved
+// at the same time, the first one on this thread, and then merged.
+fn solve_chunks(
+    chunks: &[&str],
+    normalizer: &Normalizer,
+) -> (BTreeMap<String, BTreeSet<usize>>, usize) {
+    match chunks {
+        [] => (BTreeMap::new(), 0),
+        [chunk] => (solve_with(chunk, normalizer), line_count(chunk)),
+        _ => {
+            let (first, second) = chunks.split_at(chunks.len() / 2);
+            let (first, second) = thread::scope(|scope| {
+                let handle = scope.spawn(|| solve_chunks(second, normalizer));
+                (solve_chunks(first, normalizer), handle.join().unwrap())
+            });
+            merge(first, second)
+        }
+    }
+}
+
+// Appends the lines of a later partial map to an earlier one.
+fn merge(
+    (mut word_map, offset): (BTreeMap<String, BTreeSet<usize>>, usize),
+    (partial, lines): (BTreeMap<String, BTreeSet<usize>>, usize),
+) -> (BTreeMap<String, BTreeSet<usize>>, usize) {
+    for (word, chunk_lines) in partial {
+        // Shifted lines are all past the lines of the earlier map, so they
+        // are appended at the end of the set.
+        let shifted = chunk_lines.into_iter().map(|line| line + offset);
+        match word_map.get_mut(&word) {
+            Some(lines) => lines.extend(shifted),
+            None => {
+                word_map.insert(word, shifted.collect());
+            }
+        }
+    }
+    (word_map, offset + lines)
+}
+
+// Indexes a file in parallel. Plain UTF-8 files are read straight from a
+// memory map; other encodings are decoded into memory first.
+pub fn solve_file_parallel(
+    file_path: &str,
+    encoding: Option<Encoding>,
+    normalizer: &Normalizer,
+    threads: usize,
+) -> io::Result<BTreeMap<String, BTreeSet<usize>>> {
+    let file = File::open(file_path)?;
+    if file.metadata()?.len() == 0 {
+        return Ok(BTreeMap::new());
+    }
+    let mmap = unsafe { Mmap::map(&file)? };
+    let bom = detect_bom(&mmap);
+    let plain_utf8 = matches!(
+        encoding.or(bom.map(|(encoding, _)| encoding)),
+        None | Some(Encoding::Utf8)
+    );
+    if plain_utf8 {
+        let start = bom.map_or(0, |(_, len)| len);
+        if let Ok(content) = std::str::from_utf8(&mmap[start..]) {
+            return Ok(solve_parallel_with(content, normalizer, threads));
+        }
+    }
+    let content = read_file_with_encoding(file_path, encoding)?;
+    Ok(solve_parallel_with(&content, normalizer, threads))
+}
+
+// Up to `partitions` chunks of about equal size, each ending just after a
+// newline (except the last one), so no line is split between two chunks.
+fn line_chunks(text: &str, partitions: usize) -> Vec<&str> {
+    let chunk_size = text.len() / partitions + 1;
+    let bytes = text.as_bytes();
+    let mut chunks = Vec::with_capacity(partitions);
+    let mut start = 0;
+    while start < text.len() {
+        let target = (start + chunk_size).min(text.len());
+        let end = match bytes[target..].iter().position(|&b| b == b'\n') {
+            Some(newline) => target + newline + 1,
+            None => text.len(),
+        };
+        chunks.push(&text[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+// Number of lines `solve` sees in a chunk: one per newline, plus a last line
+// without one.
+fn line_count(chunk: &str) -> usize {
+    let newlines = chunk.bytes().filter(|&b| b == b'\n').count();
+    newlines + usize::from(!chunk.is_empty() && !chunk.ends_with('\n'))
+}
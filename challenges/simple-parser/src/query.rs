@@ -0,0 +1,263 @@
+use std::collections::BTreeSet;
+
+use crate::{Index, Normalizer};
+
+// A parsed boolean query. Words in the query go through the index's
+// normalizer; a word that normalizes to several terms (e.g. with hyphen
+// splitting) becomes a phrase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    Term(String),
+    Phrase(Vec<String>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    // A word with nothing left to index, e.g. a lone punctuation mark. It
+    // does not restrict the result.
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+// Grammar, with AND binding tighter than OR and AND implied between operands:
+//
+//   or      := and ("OR" and)*
+//   and     := unary (["AND"] unary)*
+//   unary   := "NOT" unary | primary
+//   primary := "(" or ")" | "\"" words "\"" | word
+//
+// so `rust AND (memory OR safety) NOT garbage` means
+// rust AND (memory OR safety) AND NOT garbage. Operators must be upper case,
+// `and`/`or`/`not` are ordinary words.
+pub fn parse_query(input: &str, normalizer: &Normalizer) -> Result<Query, String> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        normalizer,
+    };
+    let query = parser.parse_or()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(query),
+        Some(token) => Err(format!("Unexpected {:?} in query", token)),
+    }
+}
+
+// What evaluating a query needs from an index. `Index` answers from its
+// positional postings, `InternedIndex` from its interned ones.
+pub trait LineIndex {
+    fn line_count(&self) -> usize;
+    fn lines(&self, term: &str) -> BTreeSet<usize>;
+    fn phrase_lines(&self, terms: &[String]) -> BTreeSet<usize>;
+}
+
+impl LineIndex for Index {
+    fn line_count(&self) -> usize {
+        Index::line_count(self)
+    }
+
+    fn lines(&self, term: &str) -> BTreeSet<usize> {
+        Index::lines(self, term)
+    }
+
+    fn phrase_lines(&self, terms: &[String]) -> BTreeSet<usize> {
+        Index::phrase_lines(self, terms)
+    }
+}
+
+impl Query {
+    pub fn evaluate<I: LineIndex>(&self, index: &I) -> BTreeSet<usize> {
+        match self {
+            Query::Term(term) => index.lines(term),
+            Query::Phrase(terms) => index.phrase_lines(terms),
+            Query::All => all_lines(index),
+            Query::Not(query) => {
+                let excluded = query.evaluate(index);
+                all_lines(index).difference(&excluded).copied().collect()
+            }
+            Query::Or(queries) => queries
+                .iter()
+                .flat_map(|query| query.evaluate(index))
+                .collect(),
+            Query::And(queries) => {
+                let (excluded, included): (Vec<&Query>, Vec<&Query>) = queries
+                    .iter()
+                    .partition(|query| matches!(query, Query::Not(_)));
+                let mut lines = match included.split_first() {
+                    Some((first, rest)) => {
+                        rest.iter().fold(first.evaluate(index), |lines, query| {
+                            lines
+                                .intersection(&query.evaluate(index))
+                                .copied()
+                                .collect()
+                        })
+                    }
+                    None => all_lines(index),
+                };
+                for query in excluded {
+                    if let Query::Not(query) = query {
+                        let excluded = query.evaluate(index);
+                        lines.retain(|line| !excluded.contains(line));
+                    }
+                }
+                lines
+            }
+        }
+    }
+
+    // The terms a matching line is expected to contain, i.e. all terms
+    // except those under a `NOT`.
+    pub fn terms(&self) -> Vec<&str> {
+        match self {
+            Query::Term(term) => vec![term.as_str()],
+            Query::Phrase(terms) => terms.iter().map(String::as_str).collect(),
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().flat_map(Query::terms).collect()
+            }
+            Query::Not(_) | Query::All => Vec::new(),
+        }
+    }
+}
+
+fn all_lines<I: LineIndex>(index: &I) -> BTreeSet<usize> {
+    (1..=index.line_count()).collect()
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => phrase.push(c),
+                        None => return Err(format!("Unterminated phrase starting at {}", start)),
+                    }
+                }
+                tokens.push(Token::Phrase(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    normalizer: &'a Normalizer,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, String> {
+        let mut queries = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            queries.push(self.parse_and()?);
+        }
+        Ok(if queries.len() == 1 {
+            queries.pop().unwrap()
+        } else {
+            Query::Or(queries)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Query, String> {
+        let mut queries = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.pos += 1;
+                    queries.push(self.parse_unary()?);
+                }
+                Some(Token::Word(_) | Token::Phrase(_) | Token::Not | Token::Open) => {
+                    queries.push(self.parse_unary()?);
+                }
+                _ => break,
+            }
+        }
+        Ok(if queries.len() == 1 {
+            queries.pop().unwrap()
+        } else {
+            Query::And(queries)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Query::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Query, String> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Open) => {
+                let query = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("Missing closing parenthesis in query".to_string());
+                }
+                self.pos += 1;
+                Ok(query)
+            }
+            Some(Token::Word(text)) | Some(Token::Phrase(text)) => Ok(self.terms_query(&text)),
+            Some(token) => Err(format!("Unexpected {:?} in query", token)),
+            None => Err("Unexpected end of query".to_string()),
+        }
+    }
+
+    fn terms_query(&self, text: &str) -> Query {
+        let mut terms = self.normalizer.normalize_word(text);
+        match terms.len() {
+            0 => Query::All,
+            1 => Query::Term(terms.pop().unwrap()),
+            _ => Query::Phrase(terms),
+        }
+    }
+}
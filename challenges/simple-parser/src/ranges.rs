@@ -0,0 +1,84 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+// Displays a set of line numbers as ranges of consecutive lines, e.g.
+// `3-4, 11, 14-16`. With `max_lines`, at most that many line numbers are
+// shown and the rest is summed up: `3-4, 11, …and 7 more`.
+#[derive(Debug, Clone, Copy)]
+pub struct LineRanges<'a> {
+    lines: &'a BTreeSet<usize>,
+    max_lines: Option<usize>,
+}
+
+impl<'a> LineRanges<'a> {
+    pub fn new(lines: &'a BTreeSet<usize>) -> Self {
+        LineRanges {
+            lines,
+            max_lines: None,
+        }
+    }
+
+    pub fn max_lines(self, max_lines: Option<usize>) -> Self {
+        LineRanges { max_lines, ..self }
+    }
+}
+
+impl fmt::Display for LineRanges<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut remaining = self.max_lines.unwrap_or(usize::MAX);
+        let mut shown = 0;
+        for (i, (first, last)) in line_ranges(self.lines.iter().copied())
+            .into_iter()
+            .enumerate()
+        {
+            if remaining == 0 {
+                break;
+            }
+            let last = last.min(first.saturating_add(remaining - 1));
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            if first == last {
+                write!(f, "{}", first)?;
+            } else {
+                write!(f, "{}-{}", first, last)?;
+            }
+            remaining -= last - first + 1;
+            shown += last - first + 1;
+        }
+        if shown < self.lines.len() {
+            if shown > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "…and {} more", self.lines.len() - shown)?;
+        }
+        Ok(())
+    }
+}
+
+// Groups ascending line numbers into (first, last) runs of consecutive lines.
+pub fn line_ranges(lines: impl IntoIterator<Item = usize>) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for line in lines {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == line => *last = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+}
+
+// One page of a listing: items `(page - 1) * page_size..page * page_size`,
+// with pages counted from 1.
+pub fn page<T>(items: &[T], page: usize, page_size: usize) -> &[T] {
+    let start = page
+        .saturating_sub(1)
+        .saturating_mul(page_size)
+        .min(items.len());
+    let end = start.saturating_add(page_size).min(items.len());
+    &items[start..end]
+}
+
+pub fn page_count(items: usize, page_size: usize) -> usize {
+    items.div_ceil(page_size.max(1))
+}
@@ -0,0 +1,186 @@
+use std::cmp::Ordering;
+
+use crate::{Corpus, DocId, Index, Normalizer};
+
+// Okapi BM25 parameters: `k1` limits how much repeating a term raises the
+// score, `b` how strongly long lines or documents are penalized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bm25 {
+    pub k1: f64,
+    pub b: f64,
+}
+
+impl Default for Bm25 {
+    fn default() -> Self {
+        Bm25 { k1: 1.2, b: 0.75 }
+    }
+}
+
+impl Bm25 {
+    // `units` is the number of lines or documents, `containing` how many of
+    // them contain the term.
+    fn idf(&self, units: usize, containing: usize) -> f64 {
+        let (n, df) = (units as f64, containing as f64);
+        ((n - df + 0.5) / (df + 0.5) + 1.0).ln()
+    }
+
+    fn score(&self, idf: f64, frequency: usize, length: usize, average_length: f64) -> f64 {
+        let tf = frequency as f64;
+        let norm = if average_length > 0.0 {
+            length as f64 / average_length
+        } else {
+            0.0
+        };
+        idf * tf * (self.k1 + 1.0) / (tf + self.k1 * (1.0 - self.b + self.b * norm))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineHit {
+    pub document: DocId,
+    pub line: usize,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DocumentHit {
+    pub document: DocId,
+    pub score: f64,
+}
+
+// The distinct terms of a free-text query, normalized like the indexed text.
+fn query_terms(query: &str, normalizer: &Normalizer) -> Vec<String> {
+    let mut terms: Vec<String> = query
+        .split_whitespace()
+        .flat_map(|word| normalizer.normalize_word(word))
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+// Ranks the lines of one text, each line being a BM25 "document". The
+// returned hits have `document` set to 0. Term frequencies and line lengths
+// come from the positions, so the index has to be built with them.
+pub fn rank_lines(index: &Index, query: &str, k: usize, params: &Bm25) -> Vec<LineHit> {
+    let corpus = [(0, index)];
+    rank_lines_in(&corpus, query_terms(query, index.normalizer()), k, params)
+}
+
+// Ranks the lines of all documents of a corpus together.
+pub fn rank_corpus_lines(corpus: &Corpus, query: &str, k: usize, params: &Bm25) -> Vec<LineHit> {
+    let indexes: Vec<(DocId, &Index)> = corpus
+        .documents()
+        .map(|(id, document)| (id, &document.index))
+        .collect();
+    rank_lines_in(&indexes, query_terms(query, corpus.normalizer()), k, params)
+}
+
+fn rank_lines_in(
+    indexes: &[(DocId, &Index)],
+    terms: Vec<String>,
+    k: usize,
+    params: &Bm25,
+) -> Vec<LineHit> {
+    let lines: usize = indexes.iter().map(|(_, index)| index.line_count()).sum();
+    let total_terms: usize = indexes.iter().map(|(_, index)| index.total_terms()).sum();
+    let average_length = if lines == 0 {
+        0.0
+    } else {
+        total_terms as f64 / lines as f64
+    };
+
+    let mut hits: Vec<LineHit> = Vec::new();
+    for term in &terms {
+        let containing: usize = indexes
+            .iter()
+            .map(|(_, index)| index.positions(term).map_or(0, |lines| lines.len()))
+            .sum();
+        let idf = params.idf(lines, containing);
+        for &(document, index) in indexes {
+            for (&line, positions) in index.positions(term).into_iter().flatten() {
+                let score = params.score(
+                    idf,
+                    positions.len(),
+                    index.line_length(line),
+                    average_length,
+                );
+                hits.push(LineHit {
+                    document,
+                    line,
+                    score,
+                });
+            }
+        }
+    }
+    hits.sort_by_key(|hit| (hit.document, hit.line));
+    hits.dedup_by(|next, hit| {
+        let same = next.document == hit.document && next.line == hit.line;
+        if same {
+            hit.score += next.score;
+        }
+        same
+    });
+    hits.sort_by(|a, b| {
+        by_score(a.score, b.score).then((a.document, a.line).cmp(&(b.document, b.line)))
+    });
+    hits.truncate(k);
+    hits
+}
+
+// Ranks whole documents: term frequencies and lengths are summed over all
+// lines of a document.
+pub fn rank_documents(corpus: &Corpus, query: &str, k: usize, params: &Bm25) -> Vec<DocumentHit> {
+    let terms = query_terms(query, corpus.normalizer());
+    let documents = corpus.len();
+    let total_terms: usize = corpus
+        .documents()
+        .map(|(_, document)| document.index.total_terms())
+        .sum();
+    let average_length = if documents == 0 {
+        0.0
+    } else {
+        total_terms as f64 / documents as f64
+    };
+
+    let idfs: Vec<(&String, f64)> = terms
+        .iter()
+        .map(|term| {
+            let containing = corpus
+                .documents()
+                .filter(|(_, document)| document.index.positions(term).is_some())
+                .count();
+            (term, params.idf(documents, containing))
+        })
+        .collect();
+
+    let mut hits: Vec<DocumentHit> = Vec::new();
+    for (id, document) in corpus.documents() {
+        let index = &document.index;
+        let score: f64 = idfs
+            .iter()
+            .filter(|(term, _)| index.positions(term).is_some())
+            .map(|&(term, idf)| {
+                params.score(
+                    idf,
+                    index.term_count(term),
+                    index.total_terms(),
+                    average_length,
+                )
+            })
+            .sum();
+        if score > 0.0 {
+            hits.push(DocumentHit {
+                document: id,
+                score,
+            });
+        }
+    }
+    hits.sort_by(|a, b| by_score(a.score, b.score).then(a.document.cmp(&b.document)));
+    hits.truncate(k);
+    hits
+}
+
+fn by_score(a: f64, b: f64) -> Ordering {
+    b.total_cmp(&a)
+}
@@ -0,0 +1,366 @@
+use rust_coding_challenges::encoding::Encoding;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::RwLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{
+    files_below, rank_corpus_lines, read_file_with_encoding, Bm25, Corpus, Normalizer, TextStats,
+};
+
+pub const DEFAULT_TOP: usize = 10;
+
+// An HTTP response with a JSON body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, value: Value) -> Self {
+        Response {
+            status,
+            body: serde_json::to_string_pretty(&value).unwrap(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response::json(status, json!({ "error": message }))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+type Stamp = (u64, Option<SystemTime>);
+
+struct State {
+    corpus: Corpus,
+    // Size and modification time of every indexed file when it was read.
+    stamps: BTreeMap<String, Stamp>,
+    // The text every file was indexed from, so hits are answered with the
+    // lines that were ranked even if the file changed since.
+    texts: BTreeMap<String, Text>,
+    checked: Instant,
+}
+
+struct Text {
+    content: String,
+    // Byte offset where every line starts.
+    line_starts: Vec<usize>,
+}
+
+impl Text {
+    fn new(content: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(
+                content
+                    .match_indices('\n')
+                    .map(|(i, _)| i + 1)
+                    .filter(|&start| start < content.len()),
+            )
+            .collect();
+        Text {
+            content,
+            line_starts,
+        }
+    }
+
+    // The text of a line counted from 1, without its line ending.
+    fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.content.len());
+        let text = &self.content[start..end];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+}
+
+// The corpus behind `simple-parser serve`, answering
+//
+//   GET /search?q=<query>[&top=<k>]   best matching lines, ranked with BM25
+//   GET /word/<word>                  lines of a word in every document
+//   GET /stats                        statistics of the corpus
+//
+// with JSON. Before a request is answered, and at most once per
+// `reload_interval`, the files are checked for changes: changed and new files
+// are indexed again and deleted ones dropped. `handle` answers a request
+// without any networking, `serve` answers them on a listener.
+pub struct SearchServer {
+    paths: Vec<String>,
+    encoding: Option<Encoding>,
+    state: RwLock<State>,
+    pub reload_interval: Duration,
+}
+
+impl SearchServer {
+    pub fn open(
+        paths: &[String],
+        encoding: Option<Encoding>,
+        normalizer: Normalizer,
+    ) -> io::Result<Self> {
+        let server = SearchServer {
+            paths: paths.to_vec(),
+            encoding,
+            state: RwLock::new(State {
+                corpus: Corpus::with_positions(normalizer),
+                stamps: BTreeMap::new(),
+                texts: BTreeMap::new(),
+                checked: Instant::now(),
+            }),
+            reload_interval: Duration::from_secs(1),
+        };
+        server.reload()?;
+        Ok(server)
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.state.read().unwrap().corpus.len()
+    }
+
+    // Brings the corpus up to date with the files, returning the names of the
+    // documents that were added, changed or removed.
+    pub fn reload(&self) -> io::Result<Vec<String>> {
+        let mut state = self.state.write().unwrap();
+        state.checked = Instant::now();
+        let mut files = Vec::new();
+        for path in &self.paths {
+            files.extend(files_below(path)?);
+        }
+        let mut stamps = BTreeMap::new();
+        let mut changed = Vec::new();
+        for file in files {
+            // A file deleted since the directory was listed counts as removed.
+            let Ok(metadata) = fs::metadata(&file) else {
+                continue;
+            };
+            let stamp = (metadata.len(), metadata.modified().ok());
+            if state.stamps.get(&file) != Some(&stamp) {
+                // A file that cannot be read, e.g. one caught in the middle
+                // of being written, keeps what was indexed of it before and
+                // is tried again on the next reload.
+                let indexed = read_file_with_encoding(&file, self.encoding).and_then(|content| {
+                    state.corpus.add_document(&file, content.as_bytes())?;
+                    Ok(content)
+                });
+                match indexed {
+                    Ok(content) => {
+                        state.texts.insert(file.clone(), Text::new(content));
+                    }
+                    Err(e) => {
+                        eprintln!("Error indexing '{}': {}", file, e);
+                        if let Some(&old) = state.stamps.get(&file) {
+                            stamps.insert(file, old);
+                        }
+                        continue;
+                    }
+                }
+                changed.push(file.clone());
+            }
+            stamps.insert(file, stamp);
+        }
+        for file in state
+            .stamps
+            .keys()
+            .filter(|file| !stamps.contains_key(*file))
+            .cloned()
+            .collect::<Vec<_>>()
+        {
+            if let Some(id) = state.corpus.find(&file) {
+                state.corpus.remove_document(id);
+            }
+            state.texts.remove(&file);
+            changed.push(file);
+        }
+        state.stamps = stamps;
+        Ok(changed)
+    }
+
+    // Answers a request for `target`, the path and query string of the URL.
+    pub fn handle(&self, method: &str, target: &str) -> Response {
+        if method != "GET" {
+            return Response::error(405, "only GET is supported");
+        }
+        let due = self.state.read().unwrap().checked.elapsed() >= self.reload_interval;
+        if due {
+            if let Err(e) = self.reload() {
+                return Response::error(500, &format!("reloading failed: {}", e));
+            }
+        }
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let params: BTreeMap<String, String> = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key, true), percent_decode(value, true))
+            })
+            .collect();
+        let state = self.state.read().unwrap();
+        match path {
+            "/search" => search(&state, &params),
+            "/stats" => {
+                let top = params
+                    .get("top")
+                    .and_then(|top| top.parse().ok())
+                    .unwrap_or(DEFAULT_TOP);
+                let stats = TextStats::from_corpus(&state.corpus, top);
+                Response::json(
+                    200,
+                    json!({ "documents": state.corpus.len(), "stats": stats.to_json_value() }),
+                )
+            }
+            _ => match path.strip_prefix("/word/") {
+                Some(word) if !word.is_empty() => {
+                    word_postings(&state.corpus, &percent_decode(word, false))
+                }
+                _ => Response::error(
+                    404,
+                    "unknown endpoint, try /search?q=, /word/<word> or /stats",
+                ),
+            },
+        }
+    }
+
+    // Answers connections, each on its own thread.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                // Failing to accept one connection, e.g. when it was aborted
+                // or file descriptors ran out, does not stop the server.
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        eprintln!("Error accepting connection: {}", e);
+                        continue;
+                    }
+                };
+                scope.spawn(move || {
+                    if let Err(e) = self.handle_connection(stream) {
+                        eprintln!("Error handling request: {}", e);
+                    }
+                });
+            }
+            Ok(())
+        })
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Headers are not used; they are read so the client sees its whole
+        // request consumed.
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+            header.clear();
+        }
+        let response = match request_line
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [method, target, _version] => self.handle(method, target),
+            _ => Response::error(400, "malformed request line"),
+        };
+        let mut writer = &stream;
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.status,
+            response.reason(),
+            response.body.len(),
+            response.body
+        )?;
+        writer.flush()
+    }
+}
+
+fn search(state: &State, params: &BTreeMap<String, String>) -> Response {
+    let Some(query) = params.get("q").filter(|query| !query.trim().is_empty()) else {
+        return Response::error(400, "missing query parameter q");
+    };
+    let top = params
+        .get("top")
+        .and_then(|top| top.parse().ok())
+        .unwrap_or(DEFAULT_TOP);
+    let hits: Vec<Value> = rank_corpus_lines(&state.corpus, query, top, &Bm25::default())
+        .iter()
+        .map(|hit| {
+            let name = &state.corpus.document(hit.document).unwrap().name;
+            json!({
+                "document": name,
+                "line": hit.line,
+                "score": hit.score,
+                "text": state.texts.get(name).and_then(|text| text.line(hit.line)),
+            })
+        })
+        .collect();
+    Response::json(200, json!({ "query": query, "hits": hits }))
+}
+
+fn word_postings(corpus: &Corpus, word: &str) -> Response {
+    let terms: BTreeMap<String, BTreeMap<&str, Vec<usize>>> = corpus
+        .normalizer()
+        .normalize_word(word)
+        .into_iter()
+        .map(|term| {
+            let postings = corpus
+                .postings(&term)
+                .into_iter()
+                .map(|(id, lines)| {
+                    (
+                        corpus.document(id).unwrap().name.as_str(),
+                        lines.into_iter().collect(),
+                    )
+                })
+                .collect();
+            (term, postings)
+        })
+        .collect();
+    Response::json(200, json!({ "word": word, "terms": terms }))
+}
+
+// Decodes `%XX` escapes, and in query strings `+` as a space. Invalid escapes
+// are kept as they are.
+pub fn percent_decode(text: &str, plus_as_space: bool) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            // `from_str_radix` alone would also take a sign, as in "%+4".
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (_, Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', None) if plus_as_space => decoded.push(b' '),
+            (byte, None) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
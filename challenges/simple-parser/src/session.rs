@@ -0,0 +1,188 @@
+use rust_coding_challenges::encoding::Encoding;
+use std::collections::BTreeSet;
+use std::io;
+
+use crate::{parse_query, read_file_with_encoding, Index, LineRanges, Normalizer};
+
+pub const SESSION_COMMANDS: [&str; 10] = [
+    "find", "prefix", "and", "or", "query", "show", "stats", "reload", "help", "quit",
+];
+
+pub const SESSION_HELP: &str = "Commands:
+  find <word>...    lines of each word
+  prefix <prefix>   words starting with a prefix and their lines
+  and <word>...     lines containing all words
+  or <word>...      lines containing any of the words
+  query <query>     boolean query, e.g. rust AND (memory OR safety)
+  show <line>       print a line
+  stats             size of the index
+  reload            re-read the file and rebuild the index
+  help              this text
+  quit              leave the shell";
+
+// The state behind `simple-parser --interactive`: a file indexed once, and
+// the commands of the shell run against it. Every command returns the text
+// to print, so the shell itself only deals with line editing.
+pub struct Session {
+    file_path: String,
+    encoding: Option<Encoding>,
+    normalizer: Normalizer,
+    index: Index,
+    lines: Vec<String>,
+}
+
+impl Session {
+    pub fn open(
+        file_path: &str,
+        encoding: Option<Encoding>,
+        normalizer: Normalizer,
+    ) -> io::Result<Self> {
+        let mut session = Session {
+            file_path: file_path.to_string(),
+            encoding,
+            normalizer,
+            index: Index::default(),
+            lines: Vec::new(),
+        };
+        session.reload()?;
+        Ok(session)
+    }
+
+    pub fn reload(&mut self) -> io::Result<()> {
+        let content = read_file_with_encoding(&self.file_path, self.encoding)?;
+        self.index = Index::from_text_with_positions(&content, &self.normalizer);
+        self.lines = content.lines().map(str::to_string).collect();
+        Ok(())
+    }
+
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    // Indexed terms starting with `prefix`, for tab completion.
+    pub fn complete(&self, prefix: &str) -> Vec<&str> {
+        self.index.terms_with_prefix(prefix)
+    }
+
+    pub fn execute(&mut self, command: &str) -> Result<String, String> {
+        let command = command.trim();
+        let (name, rest) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        let rest = rest.trim();
+        match name {
+            "find" => Ok(self
+                .terms(rest)?
+                .iter()
+                .map(|term| {
+                    format!(
+                        "Word: '{}', Lines: {}",
+                        term,
+                        LineRanges::new(&self.index.lines(term))
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")),
+            "prefix" => {
+                if rest.is_empty() {
+                    return Err("Expected a prefix".to_string());
+                }
+                let terms = self
+                    .index
+                    .terms_with_prefix(&self.normalizer.normalize_pattern(rest));
+                if terms.is_empty() {
+                    return Ok(format!("No words start with '{}'", rest));
+                }
+                Ok(terms
+                    .iter()
+                    .map(|term| {
+                        format!(
+                            "Word: '{}', Lines: {}",
+                            term,
+                            LineRanges::new(&self.index.lines(term))
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            "and" => {
+                let sets: Vec<BTreeSet<usize>> = self
+                    .terms(rest)?
+                    .iter()
+                    .map(|term| self.index.lines(term))
+                    .collect();
+                let lines = sets
+                    .split_first()
+                    .map(|(first, rest)| {
+                        rest.iter().fold(first.clone(), |lines, set| {
+                            lines.intersection(set).copied().collect()
+                        })
+                    })
+                    .unwrap_or_default();
+                Ok(format!("Lines: {}", LineRanges::new(&lines)))
+            }
+            "or" => {
+                let lines: BTreeSet<usize> = self
+                    .terms(rest)?
+                    .iter()
+                    .flat_map(|term| self.index.lines(term))
+                    .collect();
+                Ok(format!("Lines: {}", LineRanges::new(&lines)))
+            }
+            "query" => {
+                let query = parse_query(rest, &self.normalizer)?;
+                Ok(format!(
+                    "Lines: {}",
+                    LineRanges::new(&query.evaluate(&self.index))
+                ))
+            }
+            "show" => {
+                let line: usize = rest
+                    .parse()
+                    .map_err(|_| format!("Not a line number: '{}'", rest))?;
+                match line.checked_sub(1).and_then(|i| self.lines.get(i)) {
+                    Some(text) => Ok(format!("{:>6}: {}", line, text)),
+                    None => Err(format!(
+                        "Line {} is out of range (1-{})",
+                        line,
+                        self.lines.len()
+                    )),
+                }
+            }
+            "stats" => Ok(format!(
+                "File: '{}', lines: {}, words: {}, distinct words: {}",
+                self.file_path,
+                self.index.line_count(),
+                self.index.total_terms(),
+                self.index.terms().count()
+            )),
+            "reload" => {
+                self.reload()
+                    .map_err(|err| format!("Cannot reload '{}': {}", self.file_path, err))?;
+                Ok(format!(
+                    "Reloaded '{}': {} lines, {} distinct words",
+                    self.file_path,
+                    self.index.line_count(),
+                    self.index.terms().count()
+                ))
+            }
+            "help" => Ok(SESSION_HELP.to_string()),
+            "" => Ok(String::new()),
+            _ => Err(format!(
+                "Unknown command '{}', type 'help' for a list",
+                name
+            )),
+        }
+    }
+
+    fn terms(&self, words: &str) -> Result<Vec<String>, String> {
+        let terms: Vec<String> = words
+            .split_whitespace()
+            .flat_map(|word| self.normalizer.normalize_word(word))
+            .collect();
+        if terms.is_empty() {
+            return Err("Expected at least one word".to_string());
+        }
+        Ok(terms)
+    }
+}
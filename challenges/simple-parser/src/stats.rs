@@ -0,0 +1,238 @@
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::{Corpus, Index};
+
+pub const COVERAGE_PERCENTILES: [usize; 5] = [50, 75, 90, 99, 100];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordStat {
+    pub word: String,
+    // Lines the word appears in.
+    pub lines: usize,
+    // Occurrences of the word.
+    pub count: usize,
+}
+
+// Least squares fit of ln(count) against ln(rank) over all words ranked by
+// count. Text that follows Zipf's law has a slope close to -1.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ZipfFit {
+    pub slope: f64,
+    pub intercept: f64,
+    pub r_squared: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextStats {
+    pub lines: usize,
+    pub tokens: usize,
+    pub vocabulary: usize,
+    // Words that occur exactly once.
+    pub hapax_legomena: usize,
+    // The most widespread words, by the number of lines they appear in.
+    pub top_words: Vec<WordStat>,
+    pub zipf: Option<ZipfFit>,
+    // (percentile, lines): that percentage of the words appear in at most
+    // that many lines.
+    pub coverage_percentiles: Vec<(usize, usize)>,
+}
+
+impl TextStats {
+    pub fn from_index(index: &Index, top: usize) -> Self {
+        let words = index.terms().map(|term| WordStat {
+            word: term.to_string(),
+            lines: index.positions(term).map_or(0, BTreeMap::len),
+            count: index.term_count(term),
+        });
+        TextStats::from_words(words.collect(), index.line_count(), top)
+    }
+
+    // Lines are counted over all documents, so a word's line coverage is the
+    // number of lines it appears in across the corpus.
+    pub fn from_corpus(corpus: &Corpus, top: usize) -> Self {
+        let mut words: BTreeMap<&str, WordStat> = BTreeMap::new();
+        for (_, document) in corpus.documents() {
+            let index = &document.index;
+            for term in index.terms() {
+                let stat = words.entry(term).or_insert_with(|| WordStat {
+                    word: term.to_string(),
+                    lines: 0,
+                    count: 0,
+                });
+                stat.lines += index.positions(term).map_or(0, BTreeMap::len);
+                stat.count += index.term_count(term);
+            }
+        }
+        let lines = corpus
+            .documents()
+            .map(|(_, document)| document.index.line_count())
+            .sum();
+        TextStats::from_words(words.into_values().collect(), lines, top)
+    }
+
+    pub fn from_words(mut words: Vec<WordStat>, lines: usize, top: usize) -> Self {
+        let tokens = words.iter().map(|word| word.count).sum();
+        let hapax_legomena = words.iter().filter(|word| word.count == 1).count();
+
+        let mut coverage: Vec<usize> = words.iter().map(|word| word.lines).collect();
+        coverage.sort_unstable();
+        let coverage_percentiles = if coverage.is_empty() {
+            Vec::new()
+        } else {
+            COVERAGE_PERCENTILES
+                .iter()
+                .map(|&percentile| {
+                    (
+                        percentile,
+                        coverage[nearest_rank(percentile, coverage.len())],
+                    )
+                })
+                .collect()
+        };
+
+        let mut counts: Vec<usize> = words.iter().map(|word| word.count).collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        let zipf = zipf_fit(&counts);
+
+        words.sort_by(|a, b| {
+            b.lines
+                .cmp(&a.lines)
+                .then(b.count.cmp(&a.count))
+                .then(a.word.cmp(&b.word))
+        });
+        let vocabulary = words.len();
+        words.truncate(top);
+        TextStats {
+            lines,
+            tokens,
+            vocabulary,
+            hapax_legomena,
+            top_words: words,
+            zipf,
+            coverage_percentiles,
+        }
+    }
+
+    pub fn type_token_ratio(&self) -> f64 {
+        if self.tokens == 0 {
+            return 0.0;
+        }
+        self.vocabulary as f64 / self.tokens as f64
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_json_value()).unwrap()
+    }
+
+    pub fn to_json_value(&self) -> serde_json::Value {
+        json!({
+            "lines": self.lines,
+            "tokens": self.tokens,
+            "vocabulary": self.vocabulary,
+            "type_token_ratio": self.type_token_ratio(),
+            "hapax_legomena": self.hapax_legomena,
+            "zipf": self.zipf.map(|zipf| json!({
+                "slope": zipf.slope,
+                "intercept": zipf.intercept,
+                "r_squared": zipf.r_squared,
+            })),
+            "coverage_percentiles": self
+                .coverage_percentiles
+                .iter()
+                .map(|&(percentile, lines)| json!({"percentile": percentile, "lines": lines}))
+                .collect::<Vec<_>>(),
+            "top_words": self
+                .top_words
+                .iter()
+                .map(|word| json!({"word": word.word, "lines": word.lines, "count": word.count}))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!(
+            "Lines: {}, tokens: {}, vocabulary: {}\n",
+            self.lines, self.tokens, self.vocabulary
+        ));
+        out.push_str(&format!(
+            "Type/token ratio: {:.4}, hapax legomena: {}\n",
+            self.type_token_ratio(),
+            self.hapax_legomena
+        ));
+        match self.zipf {
+            Some(zipf) => out.push_str(&format!(
+                "Zipf fit: slope {:.3}, R² {:.3}\n",
+                zipf.slope, zipf.r_squared
+            )),
+            None => out.push_str("Zipf fit: not enough distinct counts\n"),
+        }
+        out.push_str("\nLine coverage percentiles\n");
+        for (percentile, lines) in &self.coverage_percentiles {
+            out.push_str(&format!(
+                "{:>4}% of words appear in at most {} line(s)\n",
+                percentile, lines
+            ));
+        }
+        out.push_str("\nTop words by line coverage\n");
+        let width = self
+            .top_words
+            .iter()
+            .map(|word| word.word.chars().count())
+            .max()
+            .unwrap_or(0);
+        for word in &self.top_words {
+            let share = if self.lines == 0 {
+                0.0
+            } else {
+                word.lines as f64 * 100.0 / self.lines as f64
+            };
+            out.push_str(&format!(
+                "{:<width$}  {:>6} lines ({:>5.1}%)  {:>6} times\n",
+                word.word,
+                word.lines,
+                share,
+                word.count,
+                width = width
+            ));
+        }
+        out
+    }
+}
+
+// Index of the `percentile` in a sorted list of `len` values.
+fn nearest_rank(percentile: usize, len: usize) -> usize {
+    (percentile * len).div_ceil(100).clamp(1, len) - 1
+}
+
+// `counts` sorted from most to least frequent. A fit needs at least two
+// points and some spread in the counts.
+fn zipf_fit(counts: &[usize]) -> Option<ZipfFit> {
+    let points: Vec<(f64, f64)> = counts
+        .iter()
+        .enumerate()
+        .map(|(rank, &count)| (((rank + 1) as f64).ln(), (count as f64).ln()))
+        .collect();
+    let n = points.len() as f64;
+    if points.len() < 2 {
+        return None;
+    }
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let syy: f64 = points.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+    if syy == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    Some(ZipfFit {
+        slope,
+        intercept: mean_y - slope * mean_x,
+        r_squared: sxy * sxy / (sxx * syy),
+    })
+}
@@ -1,4 +1,16 @@
-use simple_parser::solve;
+use proptest::prelude::*;
+use rust_coding_challenges::encoding::Encoding;
+use simple_parser::{
+    column, concordance, diff_word_maps, files_below, fuzzy_terms, glob_terms, highlight,
+    identifier_parts, is_index_file, line_ranges, lookup_fuzzy, lookup_glob, lookup_prefix, page,
+    page_count, parse_query, percent_decode, prefix_terms, rank_corpus_lines, rank_documents,
+    rank_lines, read_file, read_file_with_encoding, save_index, solve, solve_file,
+    solve_file_parallel, solve_parallel, solve_parallel_with, solve_reader, solve_with,
+    sorted_entries, sorted_entries_with, write_index, Bm25, Change, Corpus, Field, FieldSplitter,
+    IncrementalIndex, Index, IndexDiff, InputFormat, InternedIndex, Language, LineRanges,
+    MappedIndex, Normalizer, Occurrence, Query, SearchServer, Session, SortOrder, SymbolTable,
+    TextStats, WordStat,
+};
 use std::collections::{BTreeMap, BTreeSet};
 
 #[test]
@@ -107,7 +119,7 @@ fn test_solve_large_input() {
     );
     expected_output.insert("a", BTreeSet::from([3, 7, 11, 36, 47]));
     expected_output.insert("programming", BTreeSet::from([3, 12, 13, 18, 44, 48]));
-    let result = solve(&input);
+    let result = solve(input);
     for (word, lines) in expected_output {
         match result.get(word) {
             Some(actual_lines) => {
@@ -121,3 +133,1767 @@ fn test_solve_large_input() {
         }
     }
 }
+
+#[test]
+fn test_read_file_utf16_with_bom() {
+    let text = "Rust’s memory\nmemory safety\n";
+    let mut bytes = vec![0xff, 0xfe];
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_le_bytes());
+    }
+    let path = std::env::temp_dir().join(format!("simple-parser-utf16-{}.txt", std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+
+    let content = read_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(content, text);
+    assert_eq!(solve(&content).get("memory"), Some(&BTreeSet::from([1, 2])));
+}
+
+#[test]
+fn test_read_file_windows_1252() {
+    let path =
+        std::env::temp_dir().join(format!("simple-parser-cp1252-{}.txt", std::process::id()));
+    std::fs::write(&path, b"caf\xe9 \x93quoted\x94\n").unwrap();
+
+    let content =
+        read_file_with_encoding(path.to_str().unwrap(), Some(Encoding::Windows1252)).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(content, "café “quoted”\n");
+}
+
+#[test]
+fn test_solve_reader_matches_solve() {
+    let input = "Apple banana cherry\r\n\
+                 BANANA dog dog cat\n\
+                 \n\
+                 ΣΊΣΥΦΟΣ Ödipus straße\n\
+                 last line without newline";
+    // A tiny buffer makes lines span several reads.
+    let reader = std::io::BufReader::with_capacity(4, input.as_bytes());
+    let result = solve_reader(reader).unwrap();
+    assert_eq!(result, solve(input));
+    assert_eq!(result.get("dog"), Some(&BTreeSet::from([2])));
+    assert_eq!(result.get("σίσυφος"), Some(&BTreeSet::from([4])));
+    assert_eq!(result.get("newline"), Some(&BTreeSet::from([5])));
+}
+
+#[test]
+fn test_solve_file_small_input() {
+    let result = solve_file("input/small.txt", None, &Normalizer::default()).unwrap();
+    let content = read_file("input/small.txt").unwrap();
+    assert_eq!(result, solve(&content));
+    assert_eq!(result.get("banana"), Some(&BTreeSet::from([1, 2, 9])));
+}
+
+#[test]
+fn test_solve_with_standard_normalizer() {
+    let input = "Code that doesn’t crash, Rust’s goal\n\
+                 Rust's lifetimes prevent memory leaks\n\
+                 (Rust) is \"fast\" -- and STRASSE is Straße\n\
+                 Compile-time checks make Rust safe";
+    let result = solve_with(input, &Normalizer::standard());
+    assert_eq!(result.get("crash"), Some(&BTreeSet::from([1])));
+    assert_eq!(result.get("rust"), Some(&BTreeSet::from([1, 2, 3, 4])));
+    assert!(!result.contains_key("rust's"));
+    assert_eq!(result.get("doesn't"), Some(&BTreeSet::from([1])));
+    assert_eq!(result.get("fast"), Some(&BTreeSet::from([3])));
+    assert_eq!(result.get("strasse"), Some(&BTreeSet::from([3])));
+    assert_eq!(result.get("compile-time"), Some(&BTreeSet::from([4])));
+    assert!(!result.contains_key("--"));
+    assert!(!result.contains_key("crash,"));
+}
+
+#[test]
+fn test_solve_with_split_hyphens() {
+    let normalizer = Normalizer {
+        split_hyphens: true,
+        ..Normalizer::standard()
+    };
+    let result = solve_with("Compile-time checks\nzero-cost abstractions", &normalizer);
+    assert_eq!(result.get("compile"), Some(&BTreeSet::from([1])));
+    assert_eq!(result.get("time"), Some(&BTreeSet::from([1])));
+    assert_eq!(result.get("cost"), Some(&BTreeSet::from([2])));
+    assert_eq!(
+        normalizer.normalize_word("Zero‑Cost,"),
+        vec!["zero", "cost"]
+    );
+}
+
+#[test]
+fn test_default_normalizer_keeps_punctuation() {
+    let result = solve_with("crash, Rust’s", &Normalizer::default());
+    assert_eq!(result, solve("crash, Rust’s"));
+    assert!(result.contains_key("crash,"));
+    assert!(result.contains_key("rust’s"));
+}
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("simple-parser-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn test_index_file_round_trip() {
+    let content = read_file("input/large.txt").unwrap();
+    let normalizer = Normalizer::standard();
+    let word_map = solve_with(&content, &normalizer);
+    let path = temp_path("round-trip.idx");
+    save_index(&word_map, &normalizer, &path).unwrap();
+
+    let index = MappedIndex::open(&path).unwrap();
+    assert_eq!(index.len(), word_map.len());
+    assert_eq!(index.normalizer(), &normalizer);
+    assert_eq!(index.get("rust"), word_map.get("rust").cloned());
+    assert_eq!(index.get("apple"), Some(BTreeSet::from([1, 9])));
+    assert_eq!(index.get("missing"), None);
+    assert_eq!(index.to_word_map(), word_map);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_index_file_large_line_numbers_and_empty_index() {
+    let mut word_map = BTreeMap::new();
+    word_map.insert(
+        "a".to_string(),
+        BTreeSet::from([1, 127, 128, 16_384, 5_000_000_000]),
+    );
+    word_map.insert("é".to_string(), BTreeSet::from([3]));
+    let mut bytes = Vec::new();
+    write_index(&word_map, &Normalizer::default(), &mut bytes).unwrap();
+    let path = temp_path("large-lines.idx");
+    std::fs::write(&path, &bytes).unwrap();
+    assert_eq!(MappedIndex::open(&path).unwrap().to_word_map(), word_map);
+
+    let mut empty = Vec::new();
+    write_index(&BTreeMap::new(), &Normalizer::default(), &mut empty).unwrap();
+    std::fs::write(&path, &empty).unwrap();
+    assert!(MappedIndex::open(&path).unwrap().is_empty());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_index_file_rejects_corruption() {
+    let word_map = solve("hello world\nhello rust");
+    let mut bytes = Vec::new();
+    write_index(&word_map, &Normalizer::default(), &mut bytes).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    let path = temp_path("corrupt.idx");
+    std::fs::write(&path, &bytes).unwrap();
+    let err = MappedIndex::open(&path).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    std::fs::write(&path, b"not an index at all, just some text").unwrap();
+    assert!(MappedIndex::open(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_index_file_rejects_unknown_version_and_flags() {
+    let mut bytes = Vec::new();
+    write_index(&solve("hello world"), &Normalizer::default(), &mut bytes).unwrap();
+    let path = temp_path("unknown-header.idx");
+    // Version 1 files had no format bits; they are not read with today's meaning.
+    let mut old = bytes.clone();
+    old[4..6].copy_from_slice(&1u16.to_le_bytes());
+    std::fs::write(&path, &old).unwrap();
+    let err = MappedIndex::open(&path).err().unwrap();
+    assert!(err.to_string().contains("version 1"));
+
+    for flags in [1u16 << 15, 7 << 5, 5 << 5 | 15 << 10] {
+        let mut unknown = bytes.clone();
+        unknown[6..8].copy_from_slice(&flags.to_le_bytes());
+        std::fs::write(&path, &unknown).unwrap();
+        let err = MappedIndex::open(&path).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn large_index() -> Index {
+    let content = read_file("input/large.txt").unwrap();
+    Index::from_text_with_positions(&content, &Normalizer::standard())
+}
+
+fn query_lines(index: &Index, query: &str) -> BTreeSet<usize> {
+    parse_query(query, index.normalizer())
+        .unwrap()
+        .evaluate(index)
+}
+
+#[test]
+fn test_index_matches_solve() {
+    let content = read_file("input/large.txt").unwrap();
+    let index = large_index();
+    assert_eq!(
+        index.to_word_map(),
+        solve_with(&content, &Normalizer::standard())
+    );
+    assert_eq!(index.line_count(), 48);
+    assert_eq!(index.positions("rust").unwrap().get(&3), Some(&vec![0]));
+}
+
+#[test]
+fn test_boolean_queries() {
+    let index = large_index();
+    assert_eq!(
+        query_lines(&index, "rust AND (memory OR safety) NOT garbage"),
+        BTreeSet::from([15, 16, 24, 25, 43])
+    );
+    assert_eq!(
+        query_lines(&index, "memory safety"),
+        BTreeSet::from([15, 43])
+    );
+    assert_eq!(query_lines(&index, "dog OR cat"), BTreeSet::from([2, 6]));
+    assert_eq!(query_lines(&index, "banana NOT apple"), BTreeSet::from([2]));
+    assert_eq!(query_lines(&index, "NOT rust").len(), 48 - 35);
+    assert_eq!(query_lines(&index, "Garbage,"), BTreeSet::from([22]));
+}
+
+#[test]
+fn test_phrase_queries() {
+    let index = large_index();
+    assert_eq!(
+        query_lines(&index, "\"borrow checker\""),
+        BTreeSet::from([20])
+    );
+    assert_eq!(query_lines(&index, "\"checker borrow\""), BTreeSet::new());
+    assert_eq!(
+        query_lines(&index, "\"memory safety\" OR \"thread safety\""),
+        BTreeSet::from([15, 19, 43])
+    );
+    assert_eq!(
+        parse_query("\"Borrow Checker\"", &Normalizer::default()).unwrap(),
+        Query::Phrase(vec!["borrow".to_string(), "checker".to_string()])
+    );
+}
+
+#[test]
+fn test_query_syntax_errors() {
+    let normalizer = Normalizer::default();
+    assert!(parse_query("(rust OR memory", &normalizer).is_err());
+    assert!(parse_query("rust AND", &normalizer).is_err());
+    assert!(parse_query("\"unterminated", &normalizer).is_err());
+    assert!(parse_query("rust)", &normalizer).is_err());
+}
+
+#[test]
+fn test_corpus_postings_by_document() {
+    let mut corpus = Corpus::new(Normalizer::standard());
+    let foo = corpus
+        .add_document(
+            "foo.txt",
+            "Hello world\nRust is fast\nRust, rust!".as_bytes(),
+        )
+        .unwrap();
+    let bar = corpus
+        .add_document("bar.txt", "rust everywhere\nnothing here".as_bytes())
+        .unwrap();
+
+    let postings = corpus.postings("rust");
+    assert_eq!(postings.get(&foo), Some(&BTreeSet::from([2, 3])));
+    assert_eq!(postings.get(&bar), Some(&BTreeSet::from([1])));
+
+    let word_map = corpus.word_map();
+    let words: Vec<&str> = word_map.keys().map(String::as_str).collect();
+    assert_eq!(
+        words,
+        vec![
+            "everywhere",
+            "fast",
+            "hello",
+            "here",
+            "is",
+            "nothing",
+            "rust",
+            "world"
+        ]
+    );
+    assert_eq!(
+        word_map["hello"].keys().copied().collect::<Vec<_>>(),
+        vec![foo]
+    );
+
+    let query = parse_query("rust NOT fast", corpus.normalizer()).unwrap();
+    let matches = corpus.query(&query);
+    assert_eq!(matches.get(&foo), Some(&BTreeSet::from([3])));
+    assert_eq!(matches.get(&bar), Some(&BTreeSet::from([1])));
+}
+
+#[test]
+fn test_corpus_add_replace_and_remove_documents() {
+    let mut corpus = Corpus::new(Normalizer::default());
+    let foo = corpus.add_document("foo.txt", "apple".as_bytes()).unwrap();
+    let bar = corpus
+        .add_document("bar.txt", "apple banana".as_bytes())
+        .unwrap();
+    assert_eq!(corpus.len(), 2);
+
+    // re-adding a document replaces only that document and keeps its id
+    assert_eq!(
+        corpus.add_document("foo.txt", "cherry".as_bytes()).unwrap(),
+        foo
+    );
+    assert_eq!(
+        corpus.postings("apple").keys().copied().collect::<Vec<_>>(),
+        vec![bar]
+    );
+    assert_eq!(
+        corpus
+            .postings("cherry")
+            .keys()
+            .copied()
+            .collect::<Vec<_>>(),
+        vec![foo]
+    );
+
+    assert_eq!(corpus.remove_document(bar).unwrap().name, "bar.txt");
+    assert!(corpus.postings("apple").is_empty());
+    assert!(!corpus.word_map().contains_key("banana"));
+    let baz = corpus.add_document("baz.txt", "banana".as_bytes()).unwrap();
+    assert_ne!(baz, bar);
+}
+
+#[test]
+fn test_corpus_add_directory() {
+    let dir = temp_path("corpus-dir");
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::write(dir.join("a.txt"), "rust memory").unwrap();
+    std::fs::write(dir.join("nested").join("b.txt"), "memory\nsafety memory").unwrap();
+    std::fs::write(dir.join(".hidden"), "memory").unwrap();
+
+    let mut corpus = Corpus::new(Normalizer::default());
+    let ids = corpus.add_path(dir.to_str().unwrap(), None).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(ids.len(), 2);
+    let postings = corpus.postings("memory");
+    assert_eq!(postings.get(&ids[0]), Some(&BTreeSet::from([1])));
+    assert_eq!(postings.get(&ids[1]), Some(&BTreeSet::from([1, 2])));
+    assert!(corpus.document(ids[1]).unwrap().name.ends_with("b.txt"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_files_below_skips_directory_links() {
+    let dir = temp_path("symlink-dir");
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::write(dir.join("a.txt"), "rust").unwrap();
+    std::os::unix::fs::symlink(&dir, dir.join("nested").join("loop")).unwrap();
+    std::os::unix::fs::symlink(dir.join("a.txt"), dir.join("link.txt")).unwrap();
+
+    let files = files_below(dir.to_str().unwrap()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let names: Vec<_> = files
+        .iter()
+        .map(|file| file.rsplit('/').next().unwrap())
+        .collect();
+    assert_eq!(names, ["a.txt", "link.txt"]);
+}
+
+#[test]
+fn test_index_keeps_only_lines_by_default() {
+    let text = "rust rust is rust\nrust\n\nis";
+    let index = Index::from_text(text, &Normalizer::default());
+    let positional = Index::from_text_with_positions(text, &Normalizer::default());
+    assert!(!index.has_positions());
+    assert!(positional.has_positions());
+    assert_eq!(index.to_word_map(), positional.to_word_map());
+    assert_eq!(index.to_word_map(), solve(text));
+    assert_eq!(index.line_count(), 4);
+    assert_eq!(index.lines("rust"), BTreeSet::from([1, 2]));
+    assert!(index.occurrences("rust").is_empty());
+    assert_eq!(index.total_terms(), 0);
+    assert_eq!(positional.total_terms(), 6);
+}
+
+#[test]
+fn test_index_keeps_term_frequencies_and_line_lengths() {
+    let index =
+        Index::from_text_with_positions("rust rust is rust\nrust\n\nis", &Normalizer::default());
+    assert_eq!(index.term_frequency("rust", 1), 3);
+    assert_eq!(index.term_frequency("rust", 3), 0);
+    assert_eq!(index.term_count("rust"), 4);
+    assert_eq!(index.line_length(1), 4);
+    assert_eq!(index.line_length(3), 0);
+    assert_eq!(index.total_terms(), 6);
+}
+
+#[test]
+fn test_rank_lines_bm25() {
+    let text = "rust rust rust\n\
+                rust is a language with a long line about many other things\n\
+                rust\n\
+                memory safety\n\
+                rust memory";
+    let index = Index::from_text_with_positions(text, &Normalizer::default());
+    let hits = rank_lines(&index, "rust", 10, &Bm25::default());
+    let lines: Vec<usize> = hits.iter().map(|hit| hit.line).collect();
+    // more occurrences rank higher, and among single occurrences the shorter line wins
+    assert_eq!(lines, vec![1, 3, 5, 2]);
+    assert!(hits.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+    // a line matching both terms beats lines matching only one of them
+    let hits = rank_lines(&index, "rust memory", 2, &Bm25::default());
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].line, 5);
+    assert!(rank_lines(&index, "missing", 10, &Bm25::default()).is_empty());
+}
+
+#[test]
+fn test_rank_documents_and_corpus_lines() {
+    let mut corpus = Corpus::with_positions(Normalizer::standard());
+    let guide = corpus
+        .add_document(
+            "guide.txt",
+            "Rust ownership\nThe borrow checker, ownership and borrowing".as_bytes(),
+        )
+        .unwrap();
+    let news = corpus
+        .add_document(
+            "news.txt",
+            "Rust release notes\nNew compiler version".as_bytes(),
+        )
+        .unwrap();
+    let other = corpus
+        .add_document("other.txt", "Gardening tips".as_bytes())
+        .unwrap();
+
+    let hits = rank_documents(&corpus, "ownership borrow", 10, &Bm25::default());
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].document, guide);
+
+    let hits = rank_documents(&corpus, "rust", 10, &Bm25::default());
+    let documents: Vec<_> = hits.iter().map(|hit| hit.document).collect();
+    assert!(documents.contains(&guide) && documents.contains(&news) && !documents.contains(&other));
+
+    let hits = rank_corpus_lines(&corpus, "Rust's release", 1, &Bm25::default());
+    assert_eq!((hits[0].document, hits[0].line), (news, 1));
+}
+
+#[test]
+fn test_solve_parallel_matches_solve() {
+    let content = read_file("input/large.txt").unwrap();
+    assert_eq!(solve_parallel(&content), solve(&content));
+    for threads in [1, 2, 3, 8, 100] {
+        let normalizer = Normalizer::standard();
+        assert_eq!(
+            solve_parallel_with(&content, &normalizer, threads),
+            solve_with(&content, &normalizer)
+        );
+    }
+    assert_eq!(
+        solve_file_parallel("input/large.txt", None, &Normalizer::default(), 4).unwrap(),
+        solve(&content)
+    );
+    assert!(solve_parallel_with("", &Normalizer::default(), 4).is_empty());
+}
+
+proptest! {
+    #[test]
+    fn prop_solve_parallel_matches_solve(
+        content in "(([a-c]{1,3}|é|-|'| |\r)*\n?){0,40}",
+        threads in 1usize..8,
+    ) {
+        let normalizer = Normalizer::standard();
+        prop_assert_eq!(solve_parallel_with(&content, &normalizer, threads), solve_with(&content, &normalizer));
+    }
+}
+
+#[test]
+fn test_lookup_prefix_and_glob() {
+    let word_map = solve("program programming\nprogress memory\nmemoryless\nmystery pro");
+    let terms: Vec<&str> = prefix_terms(&word_map, "prog")
+        .into_iter()
+        .map(|(term, _)| term)
+        .collect();
+    assert_eq!(terms, vec!["program", "programming", "progress"]);
+    assert_eq!(lookup_prefix(&word_map, "prog"), BTreeSet::from([1, 2]));
+    assert!(lookup_prefix(&word_map, "xyz").is_empty());
+
+    let terms: Vec<&str> = glob_terms(&word_map, "mem*ry")
+        .into_iter()
+        .map(|(term, _)| term)
+        .collect();
+    assert_eq!(terms, vec!["memory"]);
+    assert_eq!(lookup_glob(&word_map, "m*ry*"), BTreeSet::from([2, 3, 4]));
+    assert_eq!(lookup_glob(&word_map, "pr?"), BTreeSet::from([4]));
+    assert_eq!(lookup_glob(&word_map, "*ss"), BTreeSet::from([2, 3]));
+    assert_eq!(lookup_glob(&word_map, "progra*"), BTreeSet::from([1]));
+}
+
+#[test]
+fn test_normalize_lookup_pattern() {
+    let normalizer = Normalizer::standard();
+    assert_eq!(normalizer.normalize_pattern("(Straße*"), "strasse*");
+    assert_eq!(normalizer.normalize_pattern("Rust’s?"), "rust's?");
+    assert_eq!(Normalizer::default().normalize_pattern("Mem,"), "mem,");
+    let stemmed = Normalizer {
+        stem: true,
+        ..Normalizer::standard()
+    };
+    assert_eq!(stemmed.normalize_pattern("Programs"), "program");
+    assert_eq!(stemmed.normalize_pattern("Programs*"), "programs*");
+
+    let index = Index::from_text(
+        "happy programming
+STRASSE",
+        &stemmed,
+    );
+    let word_map = index.to_word_map();
+    assert_eq!(
+        lookup_prefix(&word_map, &stemmed.normalize_pattern("Happy")),
+        BTreeSet::from([1])
+    );
+    assert_eq!(
+        lookup_glob(&word_map, &stemmed.normalize_pattern("Straß*")),
+        BTreeSet::from([2])
+    );
+}
+
+#[test]
+fn test_lookup_fuzzy() {
+    let content = read_file("input/large.txt").unwrap();
+    let word_map = solve(&content);
+    let matches: Vec<(&str, usize)> = fuzzy_terms(&word_map, "rsut", 1)
+        .into_iter()
+        .map(|(term, distance, _)| (term, distance))
+        .collect();
+    assert_eq!(matches, vec![("rust", 1)]);
+    assert_eq!(lookup_fuzzy(&word_map, "rsut", 1), word_map["rust"]);
+    assert!(fuzzy_terms(&word_map, "rust", 0)
+        .iter()
+        .all(|&(term, _, _)| term == "rust"));
+    assert!(fuzzy_terms(&word_map, "qqqqqq", 2).is_empty());
+}
+
+proptest! {
+    #[test]
+    fn prop_fuzzy_terms_matches_brute_force(
+        words in proptest::collection::vec("[a-d]{0,5}", 0..30),
+        word in "[a-d]{0,5}",
+        max_distance in 0usize..3,
+    ) {
+        let word_map: BTreeMap<String, ()> = words.into_iter().map(|word| (word, ())).collect();
+        let expected: Vec<(&str, usize)> = word_map
+            .keys()
+            .map(|term| (term.as_str(), edit_distance(term, &word)))
+            .filter(|&(_, distance)| distance <= max_distance)
+            .collect();
+        let found: Vec<(&str, usize)> = fuzzy_terms(&word_map, &word, max_distance)
+            .into_iter()
+            .map(|(term, distance, _)| (term, distance))
+            .collect();
+        prop_assert_eq!(found, expected);
+    }
+}
+
+// Optimal string alignment distance, computed on the full matrix.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[test]
+fn test_stemming_keeps_surface_forms() {
+    let normalizer = Normalizer {
+        stem: true,
+        ..Normalizer::standard()
+    };
+    let text = "Programs and programming\nA program\nThe programmer";
+    let word_map = solve_with(text, &normalizer);
+    assert_eq!(word_map.get("program"), Some(&BTreeSet::from([1, 2])));
+    assert!(!word_map.contains_key("programs"));
+    assert_eq!(normalizer.normalize_word("Programming"), vec!["program"]);
+
+    let index = Index::from_text(text, &normalizer);
+    assert_eq!(index.to_word_map(), word_map);
+    assert_eq!(
+        index.surface_forms("program"),
+        BTreeSet::from([
+            "program".to_string(),
+            "programming".to_string(),
+            "programs".to_string()
+        ])
+    );
+    assert_eq!(query_lines(&index, "programs"), BTreeSet::from([1, 2]));
+    assert_eq!(
+        Index::from_text(text, &Normalizer::default())
+            .surface_forms("programs")
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_stopwords_at_index_and_query_time() {
+    let normalizer = Normalizer {
+        stopwords: std::sync::Arc::new(Normalizer::english_stopwords()),
+        ..Normalizer::standard()
+    };
+    let content = read_file("input/small.txt").unwrap();
+    let word_map = solve_with(&content, &normalizer);
+    for stopword in ["is", "a", "the", "and", "from"] {
+        assert!(!word_map.contains_key(stopword), "{} was indexed", stopword);
+    }
+    assert_eq!(word_map.get("rust"), Some(&BTreeSet::from([3, 4])));
+
+    // stopwords are skipped in queries too, and do not break up phrases
+    let index = Index::from_text_with_positions(&content, &normalizer);
+    assert_eq!(query_lines(&index, "the lazy dog"), BTreeSet::from([6]));
+    assert_eq!(
+        query_lines(&index, "\"hello world from rust\""),
+        BTreeSet::from([4])
+    );
+
+    let path = temp_path("stopwords.txt");
+    std::fs::write(&path, "# project words\nRust\n\nlanguage\n").unwrap();
+    let stopwords = Normalizer::read_stopwords(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        stopwords,
+        BTreeSet::from(["rust".to_string(), "language".to_string()])
+    );
+}
+
+#[test]
+fn test_index_file_keeps_stemming() {
+    let normalizer = Normalizer {
+        stem: true,
+        ..Normalizer::default()
+    };
+    let word_map = solve_with("running runs", &normalizer);
+    let path = temp_path("stem.idx");
+    save_index(&word_map, &normalizer, &path).unwrap();
+    let index = MappedIndex::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(index.normalizer().stem);
+    assert_eq!(index.normalizer().normalize_word("Run"), vec!["run"]);
+    assert_eq!(index.get("run"), Some(BTreeSet::from([1])));
+}
+
+#[test]
+fn test_index_file_keeps_stopwords() {
+    let normalizer = Normalizer {
+        stopwords: std::sync::Arc::new(Normalizer::english_stopwords()),
+        ..Normalizer::standard()
+    };
+    let word_map = solve_with("The borrow checker\nit's the compiler", &normalizer);
+    let path = temp_path("stopwords.idx");
+    save_index(&word_map, &normalizer, &path).unwrap();
+    let index = MappedIndex::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(index.normalizer(), &normalizer);
+    assert!(index.normalizer().normalize_word("The").is_empty());
+    assert_eq!(index.get("compiler"), Some(BTreeSet::from([2])));
+}
+
+#[test]
+fn test_index_word_offsets() {
+    let normalizer = Normalizer {
+        split_hyphens: true,
+        ..Normalizer::standard()
+    };
+    let index = Index::from_text_with_positions("Hello, wörld!\n  dog\tand  Dog-Cat", &normalizer);
+    assert_eq!(index.line_spans(1), &[(0, 5), (7, 13)]);
+    assert_eq!(
+        index.occurrences("dog"),
+        vec![
+            Occurrence {
+                line: 2,
+                position: 0,
+                start: 2,
+                end: 5
+            },
+            Occurrence {
+                line: 2,
+                position: 2,
+                start: 11,
+                end: 14
+            },
+        ]
+    );
+    // trimmed punctuation is not part of the span
+    let index = Index::from_text_with_positions("(rust), wörld!", &Normalizer::standard());
+    assert_eq!(
+        index.occurrences("rust")[0],
+        Occurrence {
+            line: 1,
+            position: 0,
+            start: 1,
+            end: 5
+        }
+    );
+    assert_eq!(index.occurrences("wörld")[0].end, "(rust), wörld".len());
+    assert_eq!(
+        column("(rust), wörld!", index.occurrences("wörld")[0].end),
+        14
+    );
+}
+
+#[test]
+fn test_index_tokens_match_solve() {
+    let content = read_file("input/large.txt").unwrap();
+    let normalizer = Normalizer {
+        split_hyphens: true,
+        stem: true,
+        stopwords: std::sync::Arc::new(Normalizer::english_stopwords()),
+        ..Normalizer::standard()
+    };
+    assert_eq!(
+        Index::from_text(&content, &normalizer).to_word_map(),
+        solve_with(&content, &normalizer)
+    );
+}
+
+proptest! {
+    #[test]
+    fn prop_index_tokens_match_solve_non_ascii(
+        content in "((İ|ß|Σ|é|\u{307}|ﬁ|’|'|\\(|\\)|-|,|[a-c]{1,3}| |\t)*\n?){0,20}",
+        split_hyphens: bool,
+        stem: bool,
+    ) {
+        let normalizer = Normalizer {
+            split_hyphens,
+            stem,
+            ..Normalizer::standard()
+        };
+        let index = Index::from_text(&content, &normalizer);
+        let word_map = solve_with(&content, &normalizer);
+        prop_assert_eq!(&index.to_word_map(), &word_map);
+        prop_assert_eq!(&InternedIndex::from_text(&content, &normalizer).to_word_map(), &word_map);
+        for (line, text) in content.lines().enumerate() {
+            for &(start, end) in index.line_spans(line + 1) {
+                prop_assert!(start < end && text.is_char_boundary(start) && text.is_char_boundary(end));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_index_tokens_trim_after_normalizing() {
+    // "İ" folds to "i" and a combining dot, which is trimmed as punctuation.
+    let index = Index::from_text_with_positions("x İ y (İ)", &Normalizer::standard());
+    assert_eq!(
+        index.to_word_map(),
+        solve_with("x İ y (İ)", &Normalizer::standard())
+    );
+    assert_eq!(index.line_spans(1), &[(0, 1), (2, 4), (5, 6), (8, 10)]);
+}
+
+#[test]
+fn test_concordance() {
+    let content = "Banana dog cat\nThe lazy dog is cute\nno match here";
+    let index = Index::from_text_with_positions(content, &Normalizer::default());
+    let lines: Vec<&str> = content.lines().collect();
+    let kwic = concordance(&index, &lines, "dog", 7);
+    assert_eq!(kwic.len(), 2);
+    assert_eq!((kwic[0].line, kwic[0].column), (1, 8));
+    assert_eq!(
+        (
+            kwic[0].left.as_str(),
+            kwic[0].keyword.as_str(),
+            kwic[0].right.as_str()
+        ),
+        ("Banana ", "dog", " cat")
+    );
+    assert_eq!(
+        (kwic[1].left.as_str(), kwic[1].right.as_str()),
+        ("e lazy ", " is cut")
+    );
+    assert_eq!(kwic[0].to_string(), "     1:8    Banana   dog   cat");
+    assert_eq!(kwic[1].to_string(), "     2:10   e lazy   dog   is cut");
+}
+
+#[test]
+fn test_highlight() {
+    let line = "The lazy dog is cute";
+    assert_eq!(
+        highlight(line, &[(9, 12)]),
+        "The lazy \x1b[1;31mdog\x1b[0m is cute"
+    );
+    assert_eq!(
+        highlight(line, &[(16, 20), (0, 3)]),
+        "\x1b[1;31mThe\x1b[0m lazy dog is \x1b[1;31mcute\x1b[0m"
+    );
+    assert_eq!(highlight(line, &[]), line);
+}
+
+#[test]
+fn test_session_commands() {
+    let path = temp_path("session.txt");
+    std::fs::write(&path, "Rust is fast\nMemory safety in Rust\nmemory leaks\n").unwrap();
+    let mut session = Session::open(path.to_str().unwrap(), None, Normalizer::standard()).unwrap();
+
+    assert_eq!(
+        session.execute("find Rust").unwrap(),
+        "Word: 'rust', Lines: 1-2"
+    );
+    assert_eq!(
+        session.execute("prefix mem").unwrap(),
+        "Word: 'memory', Lines: 2-3"
+    );
+    assert_eq!(
+        session.execute("prefix MEM,").unwrap(),
+        "Word: 'memory', Lines: 2-3"
+    );
+    assert_eq!(session.execute("and rust memory").unwrap(), "Lines: 2");
+    assert_eq!(session.execute("or fast leaks").unwrap(), "Lines: 1, 3");
+    assert_eq!(
+        session.execute("query memory NOT rust").unwrap(),
+        "Lines: 3"
+    );
+    assert_eq!(
+        session.execute("show 2").unwrap(),
+        "     2: Memory safety in Rust"
+    );
+    assert!(session.execute("show 9").is_err());
+    assert!(session
+        .execute("frobnicate")
+        .unwrap_err()
+        .contains("frobnicate"));
+    assert!(session.execute("find").is_err());
+    assert_eq!(
+        session.execute("stats").unwrap(),
+        format!(
+            "File: '{}', lines: 3, words: 9, distinct words: 7",
+            path.display()
+        )
+    );
+    assert_eq!(session.complete("me"), vec!["memory"]);
+
+    std::fs::write(
+        &path,
+        "Rust is fast\nMemory safety in Rust\nmemory leaks\nmemento\n",
+    )
+    .unwrap();
+    assert!(session.execute("reload").unwrap().contains("4 lines"));
+    assert_eq!(session.complete("me"), vec!["memento", "memory"]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_incremental_append() {
+    let mut index = IncrementalIndex::from_text("apple banana\ncherry\n", Normalizer::default());
+    assert_eq!(index.word_map(), &solve("apple banana\ncherry\n"));
+
+    let content = "apple banana\ncherry\nbanana split\n";
+    let change = index.update(content);
+    assert_eq!(
+        (change.first_line, change.removed, change.inserted),
+        (3, 0, 1)
+    );
+    assert_eq!(
+        change.terms,
+        BTreeSet::from(["banana".to_string(), "split".to_string()])
+    );
+    assert_eq!(index.word_map(), &solve(content));
+    assert_eq!(
+        index.update(content),
+        Change {
+            first_line: 4,
+            ..Change::default()
+        }
+    );
+}
+
+#[test]
+fn test_incremental_edits_renumber_lines() {
+    let before = "one\ntwo shared\nthree\nfour shared\nfive";
+    let mut index = IncrementalIndex::from_text(before, Normalizer::standard());
+
+    // a line inserted in the middle moves everything after it
+    let inserted = "one\ntwo shared\nnew line\nthree\nfour shared\nfive";
+    let change = index.update(inserted);
+    assert_eq!(
+        (change.first_line, change.removed, change.inserted),
+        (3, 0, 1)
+    );
+    assert_eq!(
+        index.word_map(),
+        &solve_with(inserted, &Normalizer::standard())
+    );
+    assert_eq!(index.word_map()["shared"], BTreeSet::from([2, 5]));
+
+    // two lines replaced by one
+    let replaced = "one\nreplaced\nthree\nfour shared\nfive";
+    let change = index.update(replaced);
+    assert_eq!(
+        (change.first_line, change.removed, change.inserted),
+        (2, 2, 1)
+    );
+    assert!(change.terms.contains("new") && change.terms.contains("replaced"));
+    assert_eq!(
+        index.word_map(),
+        &solve_with(replaced, &Normalizer::standard())
+    );
+    assert!(!index.word_map().contains_key("two"));
+
+    // deletions, an edited last line without newline, and clearing
+    for content in [
+        "three\nfour shared\nfive",
+        "three\nfour shared\nfive and more\n",
+        "x",
+        "",
+    ] {
+        index.update(content);
+        assert_eq!(
+            index.word_map(),
+            &solve_with(content, &Normalizer::standard())
+        );
+        assert_eq!(index.line_count(), content.lines().count());
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_incremental_update_matches_solve(
+        before in proptest::collection::vec("[a-c ]{0,6}", 0..12),
+        after in proptest::collection::vec("[a-c ]{0,6}", 0..12),
+    ) {
+        let (before, after) = (before.join("\n"), after.join("\n"));
+        let mut index = IncrementalIndex::from_text(&before, Normalizer::default());
+        index.update(&after);
+        prop_assert_eq!(index.word_map(), &solve(&after));
+    }
+}
+
+#[test]
+fn test_text_stats() {
+    let index = Index::from_text_with_positions(
+        "the cat\nthe dog the end\nthe cat sat\n",
+        &Normalizer::default(),
+    );
+    let stats = TextStats::from_index(&index, 2);
+    assert_eq!(
+        (
+            stats.lines,
+            stats.tokens,
+            stats.vocabulary,
+            stats.hapax_legomena
+        ),
+        (3, 9, 5, 3)
+    );
+    assert!((stats.type_token_ratio() - 5.0 / 9.0).abs() < 1e-9);
+    assert_eq!(
+        stats.top_words,
+        vec![
+            WordStat {
+                word: "the".to_string(),
+                lines: 3,
+                count: 4
+            },
+            WordStat {
+                word: "cat".to_string(),
+                lines: 2,
+                count: 2
+            },
+        ]
+    );
+    // line coverage per word, sorted: 1, 1, 1, 2, 3
+    assert_eq!(
+        stats.coverage_percentiles,
+        vec![(50, 1), (75, 2), (90, 3), (99, 3), (100, 3)]
+    );
+    let zipf = stats.zipf.unwrap();
+    assert!(zipf.slope < 0.0 && zipf.r_squared > 0.5 && zipf.r_squared <= 1.0);
+    assert!(stats.render().contains("hapax legomena: 3"));
+}
+
+#[test]
+fn test_zipf_fit_of_exact_power_law() {
+    let words = (1..=50)
+        .map(|rank| WordStat {
+            word: format!("w{}", rank),
+            lines: 1,
+            count: 1000 / rank,
+        })
+        .collect();
+    let zipf = TextStats::from_words(words, 1, 10).zipf.unwrap();
+    assert!((zipf.slope + 1.0).abs() < 0.05, "slope {}", zipf.slope);
+    assert!(zipf.r_squared > 0.99);
+    assert!(TextStats::from_words(Vec::new(), 0, 10).zipf.is_none());
+}
+
+#[test]
+fn test_corpus_stats_add_up_documents() {
+    let mut corpus = Corpus::with_positions(Normalizer::default());
+    corpus
+        .add_document("a.txt", "rust is fast\nrust".as_bytes())
+        .unwrap();
+    corpus
+        .add_document("b.txt", "rust is safe".as_bytes())
+        .unwrap();
+    let stats = TextStats::from_corpus(&corpus, 1);
+    assert_eq!((stats.lines, stats.tokens, stats.vocabulary), (3, 7, 4));
+    assert_eq!(
+        stats.top_words[0],
+        WordStat {
+            word: "rust".to_string(),
+            lines: 3,
+            count: 3
+        }
+    );
+}
+
+fn sorted_words(word_map: &BTreeMap<String, BTreeSet<usize>>, order: SortOrder) -> Vec<&str> {
+    sorted_entries(word_map, order)
+        .into_iter()
+        .map(|(word, _)| word)
+        .collect()
+}
+
+#[test]
+fn test_sort_orders() {
+    // mixed case, as in a map not built by `solve`, to show the difference between the orders
+    let word_map: BTreeMap<String, BTreeSet<usize>> = [
+        ("Zebra", vec![3]),
+        ("apple", vec![2, 3]),
+        ("épée", vec![1]),
+        ("zoo", vec![1, 2, 3]),
+        ("Apple", vec![4]),
+    ]
+    .into_iter()
+    .map(|(word, lines)| (word.to_string(), lines.into_iter().collect()))
+    .collect();
+
+    assert_eq!(
+        sorted_words(&word_map, SortOrder::Byte),
+        vec!["Apple", "Zebra", "apple", "zoo", "épée"]
+    );
+    assert_eq!(
+        sorted_words(&word_map, SortOrder::Collation),
+        vec!["apple", "Apple", "épée", "Zebra", "zoo"]
+    );
+    assert_eq!(
+        sorted_words(&word_map, SortOrder::Frequency),
+        vec!["zoo", "apple", "Apple", "Zebra", "épée"]
+    );
+    assert_eq!(
+        sorted_words(&word_map, SortOrder::FirstAppearance),
+        vec!["zoo", "épée", "apple", "Zebra", "Apple"]
+    );
+    assert_eq!(SortOrder::from_name("uca"), Some(SortOrder::Collation));
+    assert_eq!(SortOrder::from_name("random"), None);
+}
+
+#[test]
+fn test_sort_corpus_by_first_appearance() {
+    let mut corpus = Corpus::with_positions(Normalizer::default());
+    corpus
+        .add_document("a.txt", "one\ntwo three".as_bytes())
+        .unwrap();
+    corpus.add_document("b.txt", "zero one".as_bytes()).unwrap();
+    let word_map = corpus.word_map();
+    let words: Vec<&str> =
+        sorted_entries_with(&word_map, SortOrder::FirstAppearance, |word, id, line| {
+            corpus
+                .document(id)
+                .unwrap()
+                .index
+                .first_position(word, line)
+                .unwrap()
+        })
+        .into_iter()
+        .map(|(word, _)| word)
+        .collect();
+    assert_eq!(words, vec!["one", "two", "three", "zero"]);
+    let words: Vec<&str> = sorted_entries(&word_map, SortOrder::Frequency)
+        .into_iter()
+        .map(|(word, _)| word)
+        .collect();
+    assert_eq!(words[0], "one");
+}
+
+#[test]
+fn test_line_ranges_display() {
+    let lines = BTreeSet::from([3, 4, 11, 14, 15, 16]);
+    assert_eq!(
+        line_ranges(lines.iter().copied()),
+        vec![(3, 4), (11, 11), (14, 16)]
+    );
+    assert_eq!(LineRanges::new(&lines).to_string(), "3-4, 11, 14-16");
+    assert_eq!(
+        LineRanges::new(&lines).max_lines(Some(4)).to_string(),
+        "3-4, 11, 14, …and 2 more"
+    );
+    assert_eq!(
+        LineRanges::new(&lines).max_lines(Some(6)).to_string(),
+        "3-4, 11, 14-16"
+    );
+    assert_eq!(
+        LineRanges::new(&lines).max_lines(Some(0)).to_string(),
+        "…and 6 more"
+    );
+    assert_eq!(LineRanges::new(&BTreeSet::new()).to_string(), "");
+
+    let word_map = solve(&read_file("input/large.txt").unwrap());
+    assert_eq!(
+        LineRanges::new(&word_map["rust"])
+            .max_lines(Some(20))
+            .to_string(),
+        "3-4, 11, 14-16, 18, 21-23, 25-26, 28-29, 31-32, 34-37, …and 7 more"
+    );
+}
+
+#[test]
+fn test_paging() {
+    let items: Vec<usize> = (1..=7).collect();
+    assert_eq!(page(&items, 1, 3), &[1, 2, 3]);
+    assert_eq!(page(&items, 3, 3), &[7]);
+    assert!(page(&items, 4, 3).is_empty());
+    assert_eq!(page(&items, 0, 3), &[1, 2, 3]);
+    assert_eq!(page_count(items.len(), 3), 3);
+    assert_eq!(page_count(0, 3), 0);
+}
+
+#[test]
+fn test_diff_word_maps() {
+    let old = solve("the cat sat\non the mat\nhello\n");
+    let new = solve("the cat\nsat on the mat\nbanned word\n");
+    let diff = diff_word_maps(&old, &new);
+    assert_eq!(diff.added.keys().collect::<Vec<_>>(), ["banned", "word"]);
+    assert_eq!(
+        diff.removed,
+        BTreeMap::from([("hello".to_string(), BTreeSet::from([3]))])
+    );
+    assert_eq!(diff.changed.keys().collect::<Vec<_>>(), ["sat"]);
+    assert_eq!(diff.changed["sat"].added_lines(), BTreeSet::from([2]));
+    assert_eq!(diff.changed["sat"].removed_lines(), BTreeSet::from([1]));
+    assert_eq!(
+        diff.render(),
+        "+ banned: 3\n+ word: 3\n- hello: 3\n~ sat: 1 -> 2\n2 added, 1 removed, 1 changed\n"
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&diff.to_json()).unwrap();
+    assert_eq!(json["added"]["banned"], serde_json::json!([3]));
+    assert_eq!(
+        json["changed"]["sat"]["removed_lines"],
+        serde_json::json!([1])
+    );
+    assert_eq!(json["summary"]["removed"], 1);
+
+    assert!(diff_word_maps(&old, &old).is_empty());
+    assert_eq!(
+        diff_word_maps(&BTreeMap::new(), &BTreeMap::new()),
+        IndexDiff::default()
+    );
+}
+
+#[test]
+fn test_diff_against_saved_index() {
+    let word_map = solve(&read_file("input/large.txt").unwrap());
+    let index_path = temp_path("diff.idx");
+    save_index(&word_map, &Normalizer::default(), &index_path).unwrap();
+    assert!(is_index_file(&index_path));
+    assert!(!is_index_file("input/large.txt"));
+    assert!(!is_index_file(temp_path("missing.idx")));
+
+    let saved = MappedIndex::open(&index_path).unwrap().to_word_map();
+    assert!(diff_word_maps(&saved, &word_map).is_empty());
+    std::fs::remove_file(&index_path).unwrap();
+}
+
+#[test]
+fn test_symbol_table() {
+    let mut symbols = SymbolTable::new();
+    assert_eq!(symbols.get("rust"), None);
+    let words: Vec<String> = (0..1000).map(|i| format!("word{}", i)).collect();
+    for (i, word) in words.iter().enumerate() {
+        assert_eq!(symbols.intern(word), i as u32);
+    }
+    assert_eq!(symbols.intern("word7"), 7);
+    assert_eq!(symbols.intern(""), 1000);
+    assert_eq!(symbols.len(), 1001);
+    assert_eq!(symbols.get("word999"), Some(999));
+    assert_eq!(symbols.resolve(0), "word0");
+    assert_eq!(symbols.resolve(1000), "");
+    assert!(symbols
+        .iter()
+        .all(|(symbol, word)| symbols.get(word) == Some(symbol)));
+}
+
+#[test]
+fn test_interned_index_matches_solve() {
+    let content = read_file("input/large.txt").unwrap();
+    for normalizer in [Normalizer::default(), Normalizer::standard()] {
+        let index = InternedIndex::from_text(&content, &normalizer);
+        let word_map = solve_with(&content, &normalizer);
+        assert_eq!(index.to_word_map(), word_map);
+        assert_eq!(index.line_count(), content.lines().count());
+        assert_eq!(
+            index.terms().collect::<Vec<_>>(),
+            word_map.keys().map(String::as_str).collect::<Vec<_>>()
+        );
+    }
+    let index = InternedIndex::from_text(&content, &Normalizer::default());
+    assert_eq!(index.postings("nonexistent"), &[] as &[u32]);
+    assert!(index.line_symbols(0).is_empty());
+    assert!(index.line_symbols(index.line_count() + 1).is_empty());
+}
+
+#[test]
+fn test_interned_index_queries() {
+    let content = read_file("input/large.txt").unwrap();
+    let normalizer = Normalizer::standard();
+    let index = Index::from_text_with_positions(&content, &normalizer);
+    let interned = InternedIndex::from_text(&content, &normalizer);
+    for query in [
+        "rust",
+        "rust AND (memory OR safety) NOT garbage",
+        "\"memory safety\"",
+        "\"borrow checker\" OR NOT rust",
+        "\"safety memory\"",
+        "\"rust nonexistent\"",
+    ] {
+        let query = parse_query(query, &normalizer).unwrap();
+        assert_eq!(
+            query.evaluate(&interned),
+            query.evaluate(&index),
+            "{:?}",
+            query
+        );
+    }
+}
+
+fn with_format(format: InputFormat) -> Normalizer {
+    Normalizer {
+        format,
+        ..Normalizer::standard()
+    }
+}
+
+const PEOPLE_CSV: &str = "name,Home Town,notes\n\
+                          Ada Lovelace,London,\"wrote the first program, 1843\"\n\
+                          Alan Turing,Wilmslow,broke codes,extra\n\
+                          Grace Hopper,New York,\"first compiler\"\n";
+
+#[test]
+fn test_csv_format() {
+    let normalizer = with_format(InputFormat::Csv);
+    let word_map = solve_with(PEOPLE_CSV, &normalizer);
+    assert_eq!(word_map["home_town:london"], BTreeSet::from([2]));
+    assert_eq!(word_map["london"], BTreeSet::from([2]));
+    assert_eq!(word_map["notes:program"], BTreeSet::from([2]));
+    assert_eq!(word_map["notes:first"], BTreeSet::from([2, 4]));
+    assert_eq!(word_map["4:extra"], BTreeSet::from([3]));
+    // The header row is plain text.
+    assert_eq!(word_map["home"], BTreeSet::from([1]));
+    assert!(!word_map.keys().any(|word| word.starts_with("name:name")));
+    assert_eq!(
+        normalizer.normalize_word("Home_Town:London"),
+        vec!["home_town:london"]
+    );
+
+    let tsv = PEOPLE_CSV
+        .replace(',', "\t")
+        .replace("program\t", "program,");
+    assert_eq!(solve_with(&tsv, &with_format(InputFormat::Tsv)), word_map);
+
+    // Phrases match within a field, not across fields.
+    let index = Index::from_text_with_positions(PEOPLE_CSV, &normalizer);
+    assert_eq!(query_lines(&index, "\"ada lovelace\""), BTreeSet::from([2]));
+    assert_eq!(
+        query_lines(&index, "\"name:ada name:lovelace\""),
+        BTreeSet::from([2])
+    );
+    assert_eq!(query_lines(&index, "\"lovelace london\""), BTreeSet::new());
+    assert_eq!(
+        query_lines(&index, "home_town:york AND notes:compiler"),
+        BTreeSet::from([4])
+    );
+}
+
+#[test]
+fn test_field_splitter() {
+    let mut splitter = FieldSplitter::new(InputFormat::Csv);
+    let header = "a, b c \n";
+    assert_eq!(
+        splitter
+            .split(header)
+            .iter()
+            .map(|field| &header[field.start..field.end])
+            .collect::<Vec<_>>(),
+        ["a", " b c "]
+    );
+    let row = "x,\" y, z \"\r\n";
+    assert_eq!(
+        splitter.split(row),
+        [
+            Field::named(0, 1, "a".to_string()),
+            Field::named(3, 9, "b_c".to_string()),
+        ]
+    );
+
+    let mut splitter = FieldSplitter::new(InputFormat::Text);
+    assert_eq!(splitter.split("plain text\n"), [Field::text(0, 11)]);
+}
+
+#[test]
+fn test_json_lines_format() {
+    let content = "{\"user\": {\"name\": \"Ada\", \"langs\": [\"rust\", \"go\"]}, \"level\": \"error\", \"code\": 42, \"ok\": false}\n\
+                   [\"top\", {\"deep\": \"value\"}]\n\
+                   {\"broken\": \n\
+                   {\"msg\": \"say \\\"hi\\\" twice\"}\n";
+    let normalizer = with_format(InputFormat::JsonLines);
+    let word_map = solve_with(content, &normalizer);
+    assert_eq!(word_map["user.name:ada"], BTreeSet::from([1]));
+    assert_eq!(word_map["user.langs:go"], BTreeSet::from([1]));
+    assert_eq!(word_map["code:42"], BTreeSet::from([1]));
+    assert_eq!(word_map["ok:false"], BTreeSet::from([1]));
+    assert_eq!(word_map["top"], BTreeSet::from([2]));
+    assert_eq!(word_map["deep:value"], BTreeSet::from([2]));
+    assert_eq!(word_map["msg:hi"], BTreeSet::from([4]));
+    // Keys are not words of their own, and a line that is not JSON is text.
+    assert!(!word_map.contains_key("user"));
+    assert_eq!(word_map["broken"], BTreeSet::from([3]));
+    assert_eq!(
+        normalizer.normalize_word("User.Name:ADA"),
+        vec!["user.name:ada"]
+    );
+}
+
+#[test]
+fn test_markdown_format() {
+    let content = "# Title with [a link](https://example.com/secret)\n\
+                   Some text <https://hidden.example> and <not a link>.\n\
+                   ```rust\n\
+                   fn hidden_code() {}\n\
+                   ``` not a closing fence\n\
+                   ~~~\n\
+                   ```\n\
+                   After the fence.\n\
+                   [ref]: https://ref.example/path\n";
+    let word_map = solve_with(content, &with_format(InputFormat::Markdown));
+    let words: Vec<&str> = word_map.keys().map(String::as_str).collect();
+    assert_eq!(
+        words,
+        [
+            "a", "after", "and", "fence", "link", "not", "ref", "some", "text", "the", "title",
+            "with"
+        ]
+    );
+    assert_eq!(word_map["link"], BTreeSet::from([1, 2]));
+    assert_eq!(word_map["after"], BTreeSet::from([8]));
+}
+
+#[test]
+fn test_formats_agree_across_indexes() {
+    let json = "{\"a\": \"one two\", \"b\": [\"two\"]}\n{\"a\": \"three\"}\n";
+    let markdown = "one [two](http://three)\n```\nfour\n```\nfive\n";
+    let code = InputFormat::Code {
+        language: Language::Rust,
+        comments: true,
+        strings: false,
+    };
+    let python = InputFormat::Code {
+        language: Language::Python,
+        comments: false,
+        strings: true,
+    };
+    for (format, content) in [
+        (InputFormat::Csv, PEOPLE_CSV),
+        (InputFormat::JsonLines, json),
+        (InputFormat::Markdown, markdown),
+        (code, RUST_SOURCE),
+        (python, PYTHON_SOURCE),
+    ] {
+        let normalizer = with_format(format);
+        let word_map = solve_with(content, &normalizer);
+        assert_eq!(
+            Index::from_text(content, &normalizer).to_word_map(),
+            word_map,
+            "{:?}",
+            format
+        );
+        assert_eq!(
+            InternedIndex::from_text(content, &normalizer).to_word_map(),
+            word_map,
+            "{:?}",
+            format
+        );
+        assert_eq!(
+            solve_parallel_with(content, &normalizer, 3),
+            word_map,
+            "{:?}",
+            format
+        );
+
+        let mut incremental = IncrementalIndex::new(normalizer.clone());
+        incremental.update(&content[..content.find('\n').unwrap() + 1]);
+        incremental.update(content);
+        assert_eq!(incremental.word_map(), &word_map, "{:?}", format);
+
+        let mut buffer = Vec::new();
+        write_index(&word_map, &normalizer, &mut buffer).unwrap();
+        let index_path = temp_path(&format!("{:?}.idx", format));
+        std::fs::write(&index_path, &buffer).unwrap();
+        assert_eq!(
+            MappedIndex::open(&index_path).unwrap().normalizer().format,
+            format
+        );
+        std::fs::remove_file(&index_path).unwrap();
+    }
+}
+
+fn server_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+    let dir = temp_path(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, content) in files {
+        std::fs::write(dir.join(file), content).unwrap();
+    }
+    dir
+}
+
+fn json_body(body: &str) -> serde_json::Value {
+    serde_json::from_str(body).unwrap()
+}
+
+#[test]
+fn test_search_server_endpoints() {
+    let dir = server_dir(
+        "server-endpoints",
+        &[
+            ("a.txt", "rust memory safety\nplain text\n"),
+            ("b.txt", "memory leaks\n"),
+        ],
+    );
+    let mut server = SearchServer::open(
+        &[dir.to_string_lossy().to_string()],
+        None,
+        Normalizer::standard(),
+    )
+    .unwrap();
+    server.reload_interval = std::time::Duration::from_secs(3600);
+    assert_eq!(server.document_count(), 2);
+    // Hits are answered with the text that was indexed, not the file as it
+    // is now.
+    std::fs::write(
+        dir.join("a.txt"),
+        "changed
+",
+    )
+    .unwrap();
+
+    let response = server.handle("GET", "/search?q=memory+safety&top=1");
+    assert_eq!(response.status, 200);
+    let body = json_body(&response.body);
+    assert_eq!(body["query"], "memory safety");
+    assert_eq!(body["hits"].as_array().unwrap().len(), 1);
+    assert_eq!(body["hits"][0]["line"], 1);
+    assert_eq!(body["hits"][0]["text"], "rust memory safety");
+    assert!(body["hits"][0]["document"]
+        .as_str()
+        .unwrap()
+        .ends_with("a.txt"));
+
+    let body = json_body(&server.handle("GET", "/word/Memory").body);
+    let postings = body["terms"]["memory"].as_object().unwrap();
+    assert_eq!(postings.len(), 2);
+    assert!(postings
+        .values()
+        .all(|lines| lines == &serde_json::json!([1])));
+
+    let body = json_body(&server.handle("GET", "/stats?top=1").body);
+    assert_eq!(body["documents"], 2);
+    assert_eq!(body["stats"]["lines"], 3);
+    assert_eq!(body["stats"]["top_words"][0]["word"], "memory");
+
+    assert_eq!(server.handle("GET", "/search").status, 400);
+    assert_eq!(server.handle("GET", "/nowhere").status, 404);
+    assert_eq!(server.handle("GET", "/word/").status, 404);
+    assert_eq!(server.handle("POST", "/stats").status, 405);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_search_server_hot_reload() {
+    let dir = server_dir("server-reload", &[("a.txt", "old words\n")]);
+    let mut server = SearchServer::open(
+        &[dir.to_string_lossy().to_string()],
+        None,
+        Normalizer::default(),
+    )
+    .unwrap();
+    server.reload_interval = std::time::Duration::ZERO;
+    assert!(server.reload().unwrap().is_empty());
+
+    std::fs::write(dir.join("a.txt"), "new words here\n").unwrap();
+    std::fs::write(dir.join("b.txt"), "another file\n").unwrap();
+    let body = json_body(&server.handle("GET", "/word/new").body);
+    assert_eq!(body["terms"]["new"].as_object().unwrap().len(), 1);
+    assert_eq!(
+        json_body(&server.handle("GET", "/word/old").body)["terms"]["old"],
+        serde_json::json!({})
+    );
+    assert_eq!(server.document_count(), 2);
+
+    std::fs::remove_file(dir.join("b.txt")).unwrap();
+    let changed = server.reload().unwrap();
+    assert_eq!(changed.len(), 1);
+    assert!(changed[0].ends_with("b.txt"));
+    assert_eq!(server.document_count(), 1);
+
+    // An unreadable file keeps its old document and is read again later.
+    std::fs::write(dir.join("a.txt"), b"broken \xc3\x28 bytes\n").unwrap();
+    assert!(server.reload().unwrap().is_empty());
+    assert_eq!(server.handle("GET", "/word/new").status, 200);
+    assert_eq!(
+        json_body(&server.handle("GET", "/word/new").body)["terms"]["new"]
+            .as_object()
+            .unwrap()
+            .len(),
+        1
+    );
+    std::fs::write(dir.join("a.txt"), "fixed\n").unwrap();
+    assert_eq!(server.reload().unwrap().len(), 1);
+    assert_eq!(
+        json_body(&server.handle("GET", "/word/new").body)["terms"]["new"],
+        serde_json::json!({})
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_search_server_over_tcp() {
+    use std::io::{Read, Write};
+
+    let dir = server_dir("server-tcp", &[("a.txt", "hello server\n")]);
+    let server = SearchServer::open(
+        &[dir.to_string_lossy().to_string()],
+        None,
+        Normalizer::default(),
+    )
+    .unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || server.serve(listener));
+
+    let get = |target: &str| {
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    let response = get("/word/hello");
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains(&format!("Content-Length: {}", body.len())));
+    assert_eq!(json_body(body)["word"], "hello");
+    assert!(get("/missing").starts_with("HTTP/1.1 404 Not Found"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_percent_decode() {
+    assert_eq!(
+        percent_decode("memory+safety%20%22x%22", true),
+        "memory safety \"x\""
+    );
+    assert_eq!(percent_decode("a+b", false), "a+b");
+    assert_eq!(percent_decode("%C3%A9p%C3%A9e", false), "épée");
+    assert_eq!(percent_decode("100%", true), "100%");
+    assert_eq!(percent_decode("%zz%4", true), "%zz%4");
+    assert_eq!(percent_decode("%+4%-1", false), "%+4%-1");
+    assert_eq!(percent_decode("%+4", true), "% 4");
+}
+
+const RUST_SOURCE: &str = "/// Parses a query.\n\
+                           pub fn parse_query<'a>(input: &'a str) -> HTTPServerError {\n\
+                           \x20   let quote = '\\'';\n\
+                           \x20   let text = \"fn hidden_in_string\";\n\
+                           \x20   /* block comment /* nested */ still\n\
+                           \x20      inside */ let parsedValue = r#\"raw \"string\" hidden\"#;\n\
+                           \x20   let _ = b'x'; // trailing note\n\
+                           }\n";
+
+const PYTHON_SOURCE: &str = "#!/usr/bin/env python3\n\
+                             def parse_query(text):  # split the query\n\
+                             \x20   \"\"\"Docstring of a class\n\
+                             \x20   spanning lines\"\"\"\n\
+                             \x20   return f\"{text}\" + 'single' + count_items\n";
+
+#[test]
+fn test_identifier_parts() {
+    let parts = |identifier: &'static str| -> Vec<&str> {
+        identifier_parts(identifier)
+            .into_iter()
+            .map(|(start, end)| &identifier[start..end])
+            .collect()
+    };
+    assert_eq!(parts("parse_query"), ["parse", "query"]);
+    assert_eq!(parts("parseQuery"), ["parse", "Query"]);
+    assert_eq!(parts("HTTPServerError"), ["HTTP", "Server", "Error"]);
+    assert_eq!(parts("utf8Decode"), ["utf8", "Decode"]);
+    assert_eq!(parts("MAX_LINE_LEN"), ["MAX", "LINE", "LEN"]);
+    assert_eq!(parts("__private"), ["private"]);
+    assert_eq!(parts("word"), ["word"]);
+}
+
+#[test]
+fn test_code_format() {
+    let plain = InputFormat::Code {
+        language: Language::Rust,
+        comments: false,
+        strings: false,
+    };
+    let word_map = solve_with(RUST_SOURCE, &with_format(plain));
+    let words: Vec<&str> = word_map.keys().map(String::as_str).collect();
+    assert_eq!(
+        words,
+        [
+            "error",
+            "http",
+            "httpservererror",
+            "input",
+            "parse",
+            "parse_query",
+            "parsed",
+            "parsedvalue",
+            "query",
+            "quote",
+            "server",
+            "str",
+            "text",
+            "value"
+        ]
+    );
+    assert_eq!(word_map["parse"], BTreeSet::from([2]));
+    assert_eq!(word_map["value"], BTreeSet::from([6]));
+    assert_eq!(word_map["parse_query"], BTreeSet::from([2]));
+
+    let all = InputFormat::Code {
+        language: Language::Rust,
+        comments: true,
+        strings: true,
+    };
+    let normalizer = with_format(all);
+    let word_map = solve_with(RUST_SOURCE, &normalizer);
+    assert_eq!(word_map["comment:parses"], BTreeSet::from([1]));
+    assert_eq!(word_map["comment:nested"], BTreeSet::from([5]));
+    assert_eq!(word_map["comment:inside"], BTreeSet::from([6]));
+    assert_eq!(word_map["comment:trailing"], BTreeSet::from([7]));
+    assert_eq!(word_map["string:hidden_in_string"], BTreeSet::from([4]));
+    assert_eq!(word_map["string:string"], BTreeSet::from([6]));
+    // Comments and strings are kept apart from the identifiers.
+    assert!(!word_map.contains_key("nested") && !word_map.contains_key("hidden_in_string"));
+    assert!(!word_map.contains_key("fn"));
+
+    let index = Index::from_text_with_positions(RUST_SOURCE, &normalizer);
+    assert_eq!(
+        query_lines(&index, "comment:block AND parsedValue"),
+        BTreeSet::from([])
+    );
+    assert_eq!(
+        query_lines(&index, "comment:inside AND parsedValue"),
+        BTreeSet::from([6])
+    );
+    assert_eq!(query_lines(&index, "\"parse query\""), BTreeSet::from([2]));
+    let occurrences = index.occurrences("server");
+    assert_eq!(occurrences.len(), 1);
+    assert_eq!(
+        &RUST_SOURCE.lines().nth(1).unwrap()[occurrences[0].start..occurrences[0].end],
+        "Server"
+    );
+}
+
+fn code_words(source: &str, language: Language, comments: bool, strings: bool) -> Vec<String> {
+    let format = InputFormat::Code {
+        language,
+        comments,
+        strings,
+    };
+    solve_with(source, &with_format(format))
+        .into_keys()
+        .collect()
+}
+
+#[test]
+fn test_code_format_languages() {
+    assert_eq!(
+        code_words(PYTHON_SOURCE, Language::Python, false, false),
+        [
+            "count",
+            "count_items",
+            "items",
+            "parse",
+            "parse_query",
+            "query",
+            "text"
+        ]
+    );
+    assert_eq!(
+        code_words(PYTHON_SOURCE, Language::Python, true, true),
+        [
+            "comment:python3",
+            "comment:query",
+            "comment:split",
+            "comment:the",
+            "comment:usr/bin/env",
+            "count",
+            "count_items",
+            "items",
+            "parse",
+            "parse_query",
+            "query",
+            "string:a",
+            "string:class",
+            "string:docstring",
+            "string:lines",
+            "string:of",
+            "string:single",
+            "string:spanning",
+            "string:text",
+            "text"
+        ]
+    );
+    // In shell `#` starts a comment only at the start of a word.
+    let shell = "echo \"$#\" ${#files} # note here\nfi\n";
+    assert_eq!(
+        code_words(shell, Language::Shell, false, false),
+        ["echo", "files"]
+    );
+    assert_eq!(
+        code_words(shell, Language::Shell, true, false),
+        ["comment:here", "comment:note", "echo", "files"]
+    );
+    // C block comments do not nest, and C keywords are left out.
+    let c = "int main(void) { /* a /* b */ char *s = \"x\"; return 0; }\n";
+    assert_eq!(code_words(c, Language::C, false, false), ["main", "s"]);
+    assert_eq!(
+        code_words(c, Language::Rust, false, false),
+        ["int", "main", "void"]
+    );
+    let toml = "name = 'it''s' # comment\n[package.metadata]\n";
+    assert_eq!(
+        code_words(toml, Language::Toml, false, false),
+        ["metadata", "name", "package"]
+    );
+
+    assert_eq!(
+        Language::from_extension("src/lib.py"),
+        Some(Language::Python)
+    );
+    assert_eq!(Language::from_extension("Cargo.toml"), Some(Language::Toml));
+    assert_eq!(Language::from_extension("README"), None);
+    assert_eq!(Language::from_name("ts"), Some(Language::JavaScript));
+    assert!(Language::Java.keywords().contains(&"void"));
+}
@@ -0,0 +1,177 @@
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::{count_with_threads, count_words_in_chunk, count_words_in_chunk_split_whitespace};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    // `split_whitespace` over the whole text on the calling thread; every other
+    // strategy is checked against it.
+    SingleThreaded,
+    // The scoped-thread counter used by `count_words`.
+    Threaded,
+    // The scoped-thread counter with `split_whitespace` inside each chunk.
+    ThreadedSplitWhitespace,
+    // Rayon's parallel `split_whitespace` on a pool of the given size.
+    Rayon,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 4] = [
+        Strategy::SingleThreaded,
+        Strategy::Threaded,
+        Strategy::ThreadedSplitWhitespace,
+        Strategy::Rayon,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::SingleThreaded => "single-threaded",
+            Strategy::Threaded => "threaded",
+            Strategy::ThreadedSplitWhitespace => "threaded-split-whitespace",
+            Strategy::Rayon => "rayon",
+        }
+    }
+
+    pub fn is_parallel(self) -> bool {
+        self != Strategy::SingleThreaded
+    }
+
+    pub fn count_words(self, text: &str, threads: usize) -> usize {
+        self.count_words_on(text, threads, None)
+    }
+
+    // Rayon runs on `pool` if given, so a benchmark can build the pool once
+    // and leave spawning its threads out of the timing.
+    fn count_words_on(self, text: &str, threads: usize, pool: Option<&ThreadPool>) -> usize {
+        match self {
+            Strategy::SingleThreaded => text.split_whitespace().count(),
+            Strategy::Threaded => count_with_threads(text, threads, count_words_in_chunk),
+            Strategy::ThreadedSplitWhitespace => {
+                count_with_threads(text, threads, count_words_in_chunk_split_whitespace)
+            }
+            Strategy::Rayon => {
+                let count = || text.par_split_whitespace().count();
+                match pool {
+                    Some(pool) => pool.install(count),
+                    None => rayon_pool(threads).install(count),
+                }
+            }
+        }
+    }
+}
+
+fn rayon_pool(threads: usize) -> ThreadPool {
+    ThreadPoolBuilder::new().num_threads(threads).build().unwrap()
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+    pub thread_counts: Vec<usize>,
+    // Fractions of the input to benchmark, e.g. 0.25 for the first quarter.
+    pub sizes: Vec<f64>,
+    // Each measurement is the best of this many runs.
+    pub repeats: usize,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        let max_threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let thread_counts = std::iter::successors(Some(1), |&n| Some(n * 2))
+            .take_while(|&n| n < max_threads)
+            .chain([max_threads])
+            .collect();
+        BenchConfig {
+            thread_counts,
+            sizes: vec![0.25, 0.5, 1.0],
+            repeats: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub strategy: Strategy,
+    pub threads: usize,
+    pub bytes: usize,
+    pub words: usize,
+    pub elapsed: Duration,
+    // Speed-up over the same strategy on one thread, divided by the thread count.
+    pub efficiency: f64,
+}
+
+impl BenchResult {
+    pub fn throughput_mb_per_sec(&self) -> f64 {
+        self.bytes as f64 / (1024.0 * 1024.0) / self.elapsed.as_secs_f64()
+    }
+}
+
+// Runs every strategy over prefixes of `text`. Fails if any strategy counts a
+// different number of words than the single-threaded reference.
+pub fn run_bench(text: &str, config: &BenchConfig) -> Result<Vec<BenchResult>, String> {
+    let mut results = Vec::new();
+    let pools: BTreeMap<usize, ThreadPool> = config
+        .thread_counts
+        .iter()
+        .chain([&1])
+        .map(|&threads| (threads, rayon_pool(threads)))
+        .collect();
+    for &size in &config.sizes {
+        let sample = prefix(text, size);
+        let expected = Strategy::SingleThreaded.count_words(sample, 1);
+        for strategy in Strategy::ALL {
+            let thread_counts = if strategy.is_parallel() { &config.thread_counts[..] } else { &[1][..] };
+            let (baseline_words, baseline) = measure(strategy, sample, &pools[&1], config.repeats);
+            for &threads in thread_counts {
+                let (words, elapsed) = if threads == 1 {
+                    (baseline_words, baseline)
+                } else {
+                    measure(strategy, sample, &pools[&threads], config.repeats)
+                };
+                if words != expected {
+                    return Err(format!(
+                        "{} with {} threads counted {} words in {} bytes, expected {}",
+                        strategy.name(),
+                        threads,
+                        words,
+                        sample.len(),
+                        expected
+                    ));
+                }
+                let speedup = baseline.as_secs_f64() / elapsed.as_secs_f64();
+                results.push(BenchResult {
+                    strategy,
+                    threads,
+                    bytes: sample.len(),
+                    words,
+                    elapsed,
+                    efficiency: speedup / threads as f64,
+                });
+            }
+        }
+    }
+    Ok(results)
+}
+
+// `pool` has the number of threads to measure with.
+fn measure(strategy: Strategy, text: &str, pool: &ThreadPool, repeats: usize) -> (usize, Duration) {
+    let threads = pool.current_num_threads();
+    let mut best = Duration::MAX;
+    let mut words = 0;
+    for _ in 0..repeats.max(1) {
+        let start = Instant::now();
+        words = strategy.count_words_on(text, threads, Some(pool));
+        best = best.min(start.elapsed());
+    }
+    (words, best.max(Duration::from_nanos(1)))
+}
+
+fn prefix(text: &str, fraction: f64) -> &str {
+    let mut end = ((text.len() as f64 * fraction) as usize).min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
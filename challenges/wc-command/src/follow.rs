@@ -0,0 +1,145 @@
+use core::str;
+use memmap2::MmapOptions;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::count_words;
+use crate::stream::{Counts, StreamCounter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowEvent {
+    Unchanged,
+    Appended(u64),
+    Truncated,
+    Rotated,
+}
+
+// Keeps the counts of a growing file up to date. The file is counted once when
+// it is opened, afterwards only the appended bytes are read. When the file is
+// truncated or replaced by a new one (log rotation) counting starts over.
+pub struct Follower {
+    path: PathBuf,
+    file: File,
+    identity: Option<(u64, u64)>,
+    offset: u64,
+    counter: StreamCounter,
+}
+
+impl Follower {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        let metadata = file.metadata()?;
+        let mut follower = Follower {
+            path,
+            identity: file_identity(&metadata),
+            file,
+            offset: 0,
+            counter: StreamCounter::new(),
+        };
+        follower.count_from_start(metadata.len())?;
+        Ok(follower)
+    }
+
+    pub fn counts(&self) -> Counts {
+        self.counter.counts()
+    }
+
+    pub fn poll(&mut self) -> io::Result<FollowEvent> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // The old file was moved away and the new one is not there yet.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(FollowEvent::Unchanged),
+            Err(err) => return Err(err),
+        };
+
+        let identity = file_identity(&metadata);
+        if identity != self.identity {
+            self.file = File::open(&self.path)?;
+            self.identity = identity;
+            self.count_from_start(self.file.metadata()?.len())?;
+            return Ok(FollowEvent::Rotated);
+        }
+
+        let len = metadata.len();
+        if len < self.offset {
+            self.count_from_start(len)?;
+            return Ok(FollowEvent::Truncated);
+        }
+        if len == self.offset {
+            return Ok(FollowEvent::Unchanged);
+        }
+        let appended = self.read_appended()?;
+        Ok(FollowEvent::Appended(appended))
+    }
+
+    fn count_from_start(&mut self, len: u64) -> io::Result<()> {
+        self.counter.reset();
+        self.offset = 0;
+        if len == 0 {
+            return Ok(());
+        }
+        let mmap = unsafe { MmapOptions::new().len(len as usize).map(&self.file)? };
+        self.counter = count_mapped(&mmap);
+        self.offset = len;
+        // Anything written between `metadata` and `map` is picked up here.
+        self.read_appended()?;
+        Ok(())
+    }
+
+    fn read_appended(&mut self) -> io::Result<u64> {
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut appended = 0;
+        loop {
+            let read = self.file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            self.counter.feed(&buffer[..read]);
+            appended += read as u64;
+        }
+        self.offset += appended;
+        Ok(appended)
+    }
+}
+
+// The bulk of the file goes through the parallel counter; only an incomplete
+// character at the very end, or a file with invalid UTF-8, is fed through the
+// streaming counter.
+fn count_mapped(bytes: &[u8]) -> StreamCounter {
+    let (text, tail) = match str::from_utf8(bytes) {
+        Ok(text) => (text, &[][..]),
+        Err(err) if err.error_len().is_none() => {
+            let valid_up_to = err.valid_up_to();
+            let text = unsafe { str::from_utf8_unchecked(&bytes[..valid_up_to]) };
+            (text, &bytes[valid_up_to..])
+        }
+        Err(_) => {
+            let mut counter = StreamCounter::new();
+            counter.feed(bytes);
+            return counter;
+        }
+    };
+    let counts = Counts {
+        words: count_words(text),
+        lines: text.bytes().filter(|&b| b == b'\n').count(),
+        bytes: text.len(),
+    };
+    let in_word = text.chars().next_back().is_some_and(|c| !c.is_whitespace());
+    let mut counter = StreamCounter::resume(counts, in_word);
+    counter.feed(tail);
+    counter
+}
+
+#[cfg(unix)]
+fn file_identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
@@ -0,0 +1,46 @@
+use flate2::bufread::MultiGzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+pub fn detect_compression(header: &[u8]) -> Compression {
+    if header.starts_with(&GZIP_MAGIC) {
+        Compression::Gzip
+    } else if header.starts_with(&ZSTD_MAGIC) {
+        Compression::Zstd
+    } else {
+        Compression::None
+    }
+}
+
+// Wraps `reader` in the decoder matching the magic bytes at its start. Input
+// that is not compressed is passed through unchanged.
+pub fn decode_reader<'a, R: Read + 'a>(reader: R) -> io::Result<(Compression, Box<dyn Read + 'a>)> {
+    let mut reader = BufReader::new(reader);
+    let compression = detect_compression(reader.fill_buf()?);
+    let decoded: Box<dyn Read + 'a> = match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => {
+            let decoder = StreamingDecoder::new(reader)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            Box::new(decoder)
+        }
+    };
+    Ok((compression, decoded))
+}
+
+pub fn open_decoded<P: AsRef<Path>>(file_path: P) -> io::Result<(Compression, Box<dyn Read>)> {
+    decode_reader(File::open(file_path)?)
+}
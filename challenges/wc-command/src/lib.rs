@@ -1,16 +1,82 @@
 use core::str;
 use memmap2::MmapOptions;
+use rust_coding_challenges::encoding::{detect_bom, DecodeReader};
 use std::fs::File;
+use std::io::{self, Read};
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::thread;
 
+mod bench;
+mod follow;
+mod input;
+mod stats;
+mod stream;
+
+pub use bench::{run_bench, BenchConfig, BenchResult, Strategy};
+pub use follow::{FollowEvent, Follower};
+pub use rust_coding_challenges::encoding::Encoding;
+pub use input::{decode_reader, detect_compression, open_decoded, Compression};
+pub use stats::{line_stats, LineExtreme, LineStats, LINE_LENGTH_BUCKET};
+pub use stream::{count_reader, Counts, StreamCounter};
+
 pub fn read_file_as_string(file_path: &str) -> String {
-    let file = File::open(file_path).expect("Path is not valid");
-    let mmap = unsafe { MmapOptions::new().map(&file).expect("Cannot map from file") };
-    let text = unsafe { str::from_utf8_unchecked(&mmap).to_string() };
-    text
+    read_file_as_string_with_encoding(file_path, None).expect("Cannot read file")
+}
+
+pub fn read_file_as_string_with_encoding(file_path: &str, encoding: Option<Encoding>) -> io::Result<String> {
+    let file = File::open(file_path)?;
+    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    if !is_plain_utf8(&mmap, encoding) {
+        let mut bytes = Vec::new();
+        decode_text(&mmap[..], encoding)?.read_to_end(&mut bytes)?;
+        return Ok(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    let text = unsafe { str::from_utf8_unchecked(without_utf8_bom(&mmap)).to_string() };
+    Ok(text)
+}
+
+// Plain UTF-8 files are counted in parallel straight from the memory map,
+// everything else goes through the streaming pipeline.
+pub fn count_words_in_file(file_path: &str, encoding: Option<Encoding>) -> io::Result<usize> {
+    let file = File::open(file_path)?;
+    if file.metadata()?.len() == 0 {
+        return Ok(0);
+    }
+    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    if is_plain_utf8(&mmap, encoding) {
+        let text = unsafe { str::from_utf8_unchecked(without_utf8_bom(&mmap)) };
+        return Ok(count_words(text));
+    }
+    let counts = count_reader(decode_text(&mmap[..], encoding)?)?;
+    Ok(counts.words)
+}
+
+// Counts a file through the streaming pipeline, decompressing and transcoding
+// it on the fly. `Counts::bytes` is the size of the decoded UTF-8 text.
+pub fn count_file(file_path: &str, encoding: Option<Encoding>) -> io::Result<(Compression, Counts)> {
+    let (compression, reader) = open_decoded(file_path)?;
+    let counts = count_reader(DecodeReader::new(reader, encoding))?;
+    Ok((compression, counts))
+}
+
+fn decode_text<'a, R: Read + 'a>(reader: R, encoding: Option<Encoding>) -> io::Result<DecodeReader<Box<dyn Read + 'a>>> {
+    let (_, reader) = decode_reader(reader)?;
+    Ok(DecodeReader::new(reader, encoding))
+}
+
+fn is_plain_utf8(bytes: &[u8], encoding: Option<Encoding>) -> bool {
+    let encoding = encoding.or_else(|| detect_bom(bytes).map(|(encoding, _)| encoding));
+    detect_compression(bytes) == Compression::None && matches!(encoding, None | Some(Encoding::Utf8))
+}
+
+// The text after a UTF-8 BOM, which `DecodeReader` would strip as well.
+fn without_utf8_bom(bytes: &[u8]) -> &[u8] {
+    match detect_bom(bytes) {
+        Some((Encoding::Utf8, len)) => &bytes[len..],
+        _ => bytes,
+    }
 }
 
 pub fn count_words(text: &str) -> usize {
@@ -28,14 +94,20 @@ where
     F: Fn(&str) -> usize + Send + Sync + 'static,
 {
     let num_threads: usize = thread::available_parallelism().unwrap().get();
+    count_with_threads(text, num_threads, count_fn)
+}
+
+pub(crate) fn count_with_threads<F>(text: &str, num_threads: usize, count_fn: F) -> usize
+where
+    F: Fn(&str) -> usize + Send + Sync + 'static,
+{
     let total_word_count: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
     let count_fn = Arc::new(count_fn);
     let chunks = get_chunks(text, num_threads);
     thread::scope(|scope| {
-        for i in 0..chunks.len() {
+        for &chunk in chunks.iter() {
             let total_word_count_clone = Arc::clone(&total_word_count); 
             let count_fn_clone = Arc::new(&count_fn); 
-            let chunk = chunks[i];
             scope.spawn(move || {
                 let count = count_fn_clone(chunk);
                 total_word_count_clone.fetch_add(count, Ordering::Relaxed);
@@ -76,14 +148,14 @@ fn get_end_index(text: &str, i: usize, start_index: usize, partitions: usize) ->
 }
 
 
-// fn count_words_in_chunk(chunk: &str) -> usize {
-//     chunk
-//     .lines()
-//         .flat_map(|line| line.split_whitespace())
-//         .count()
-// }
+pub(crate) fn count_words_in_chunk_split_whitespace(chunk: &str) -> usize {
+    chunk
+    .lines()
+        .flat_map(|line| line.split_whitespace())
+        .count()
+}
 
-fn count_words_in_chunk(chunk: &str) -> usize {
+pub(crate) fn count_words_in_chunk(chunk: &str) -> usize {
     let mut count = 0;
     let mut in_word = false;
     for c in chunk.chars() {
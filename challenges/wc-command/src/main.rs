@@ -1,43 +1,294 @@
 
-use wc_command::{count_word_occurrences, count_words, read_file_as_string};
-use std::{env, time::Instant};
+use wc_command::{
+    count_file, count_word_occurrences, count_words_in_file, detect_compression, line_stats, read_file_as_string_with_encoding,
+    run_bench, BenchConfig, Compression, Counts, Encoding, FollowEvent, Follower,
+};
+use rust_coding_challenges::encoding::detect_bom;
+use std::{env, fs::File, io::Read, thread, time::{Duration, Instant}};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() < 2 || args[1] != "rccwc" {
-        eprintln!("Usage: rccwc -w <file_path> or rccwc -wo <word> <file_path>");
+    if args.len() < 3 || args[1] != "rccwc" {
+        eprintln!("Usage: rccwc -w [--encoding <name>] [--stats [--json] | --follow [--rate] [--interval <ms>]] <file_path>, rccwc -c [--encoding <name>] [--compressed-bytes] <file_path>, rccwc -wo <word> [--encoding <name>] <file_path> or rccwc bench [--threads <n,n,..>] [--repeat <n>] <file_path>");
         return;
     }
 
     match args[2].as_str() {
         "-w" => {
-            if args.len() != 4 {
-                eprintln!("Usage: rccwc -w <file>");
+            let options = match Options::parse(Mode::Words, &args[3..]) {
+                Ok(options) => options,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    eprintln!("Usage: rccwc -w [--encoding <name>] [--stats [--json] | --follow [--rate] [--interval <ms>]] <file>");
+                    return;
+                }
+            };
+            if options.follow {
+                let header = file_header(&options.file_path);
+                let bom = detect_bom(&header).map(|(encoding, _)| encoding);
+                if !matches!(options.encoding.or(bom), None | Some(Encoding::Utf8)) {
+                    eprintln!("--follow only supports UTF-8 files");
+                    return;
+                }
+                if detect_compression(&header) != Compression::None {
+                    eprintln!("--follow does not support compressed files");
+                    return;
+                }
+                if let Err(err) = follow(&options) {
+                    eprintln!("Cannot follow '{}': {}", options.file_path, err);
+                }
+                return;
+            }
+            if options.stats {
+                let text = match read_file_as_string_with_encoding(&options.file_path, options.encoding) {
+                    Ok(text) => text,
+                    Err(err) => {
+                        eprintln!("Cannot read '{}': {}", options.file_path, err);
+                        return;
+                    }
+                };
+                let stats = line_stats(&text);
+                if options.json {
+                    println!("{}", stats.to_json());
+                } else {
+                    print!("{}", stats.render());
+                }
                 return;
             }
             let start = Instant::now();
-            let file_path = &args[3];
-            let text = read_file_as_string(&file_path);
-            let total_words = count_words(&text);
+            let total_words = match count_words_in_file(&options.file_path, options.encoding) {
+                Ok(total_words) => total_words,
+                Err(err) => {
+                    eprintln!("Cannot read '{}': {}", options.file_path, err);
+                    return;
+                }
+            };
             println!("Total words: {}, elapsed time: {:?}", total_words, start.elapsed());
-        } 
-        "-wo" => {
-            if args.len() != 5 {
-                eprintln!("Usage: rccwc -wc <word> <file_path>");
-                return;
+        }
+        "-c" => {
+            let options = match Options::parse(Mode::Bytes, &args[3..]) {
+                Ok(options) => options,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    eprintln!("Usage: rccwc -c [--encoding <name>] [--compressed-bytes] <file>");
+                    return;
+                }
+            };
+            let start = Instant::now();
+            let total_bytes = if options.compressed_bytes {
+                std::fs::metadata(&options.file_path).map(|metadata| metadata.len() as usize)
+            } else {
+                count_file(&options.file_path, options.encoding).map(|(_, counts)| counts.bytes)
+            };
+            match total_bytes {
+                Ok(total_bytes) => println!("Total bytes: {}, elapsed time: {:?}", total_bytes, start.elapsed()),
+                Err(err) => eprintln!("Cannot read '{}': {}", options.file_path, err),
             }
+        }
+        "-wo" => {
+            let options = match Options::parse(Mode::Occurrences, args.get(4..).unwrap_or_default()) {
+                Ok(options) => options,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    eprintln!("Usage: rccwc -wo <word> [--encoding <name>] <file_path>");
+                    return;
+                }
+            };
             let start = Instant::now();
             let word = &args[3];
-            let file_path = &args[4];
-            let text = read_file_as_string(&file_path);
+            let text = match read_file_as_string_with_encoding(&options.file_path, options.encoding) {
+                Ok(text) => text,
+                Err(err) => {
+                    eprintln!("Cannot read '{}': {}", options.file_path, err);
+                    return;
+                }
+            };
             let total_occurrences = count_word_occurrences(&text, word.to_string());
             println!("Total occurrences of '{}': {}, elapsed time: {:?}", word, total_occurrences, start.elapsed());
         }
+        "bench" => {
+            let (config, file_path) = match parse_bench_args(&args[3..]) {
+                Some(parsed) => parsed,
+                None => {
+                    eprintln!("Usage: rccwc bench [--threads <n,n,..>] [--repeat <n>] <file_path>");
+                    return;
+                }
+            };
+            let text = match read_file_as_string_with_encoding(&file_path, None) {
+                Ok(text) => text,
+                Err(err) => {
+                    eprintln!("Cannot read '{}': {}", file_path, err);
+                    return;
+                }
+            };
+            match run_bench(&text, &config) {
+                Ok(results) => {
+                    println!("{:<26} {:>8} {:>12} {:>10} {:>12} {:>12} {:>10}", "strategy", "threads", "bytes", "words", "elapsed", "MB/s", "efficiency");
+                    for result in results {
+                        println!(
+                            "{:<26} {:>8} {:>12} {:>10} {:>12} {:>12.1} {:>9.0}%",
+                            result.strategy.name(),
+                            result.threads,
+                            result.bytes,
+                            result.words,
+                            format!("{:.2?}", result.elapsed),
+                            result.throughput_mb_per_sec(),
+                            result.efficiency * 100.0
+                        );
+                    }
+                }
+                Err(err) => eprintln!("Strategies disagree: {}", err),
+            }
+        }
         _ => {
-            eprintln!("Invalid option. Usage: rccwc -w <file_path> or -wo <word> <file_path>")
+            eprintln!("Invalid option. Usage: rccwc -w <file_path>, -c <file_path>, -wo <word> <file_path> or bench <file_path>")
         }
     }
-    
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Words,
+    Bytes,
+    Occurrences,
+}
+
+struct Options {
+    follow: bool,
+    stats: bool,
+    json: bool,
+    compressed_bytes: bool,
+    encoding: Option<Encoding>,
+    rate: bool,
+    interval: Option<Duration>,
+    file_path: String,
 }
 
+impl Options {
+    fn parse(mode: Mode, args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            follow: false,
+            stats: false,
+            json: false,
+            compressed_bytes: false,
+            encoding: None,
+            rate: false,
+            interval: None,
+            file_path: String::new(),
+        };
+        let mut file_path = None;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--follow" | "-f" => options.follow = true,
+                "--rate" => options.rate = true,
+                "--stats" => options.stats = true,
+                "--json" => options.json = true,
+                "--compressed-bytes" => options.compressed_bytes = true,
+                "--encoding" => {
+                    let label = iter.next().ok_or("--encoding needs a name")?;
+                    let encoding = Encoding::from_label(label).ok_or_else(|| format!("Unknown encoding '{}'", label))?;
+                    options.encoding = Some(encoding);
+                }
+                "--interval" => {
+                    let millis = iter.next().ok_or("--interval needs a number of milliseconds")?;
+                    let millis = millis.parse().map_err(|_| format!("Not a number of milliseconds: '{}'", millis))?;
+                    options.interval = Some(Duration::from_millis(millis));
+                }
+                _ if file_path.is_none() => file_path = Some(arg.clone()),
+                _ => return Err(format!("Unexpected argument '{}'", arg)),
+            }
+        }
+        options.file_path = file_path.ok_or("Missing file path")?;
+
+        // Flags that would do nothing in the chosen mode are rejected rather
+        // than ignored.
+        if (options.follow || options.stats) && mode != Mode::Words {
+            return Err("--follow and --stats only work with -w".to_string());
+        }
+        if options.follow && options.stats {
+            return Err("--follow and --stats cannot be combined".to_string());
+        }
+        if (options.rate || options.interval.is_some()) && !options.follow {
+            return Err("--rate and --interval need --follow".to_string());
+        }
+        if options.json && !options.stats {
+            return Err("--json needs --stats".to_string());
+        }
+        if options.compressed_bytes && mode != Mode::Bytes {
+            return Err("--compressed-bytes only works with -c".to_string());
+        }
+        Ok(options)
+    }
+}
+
+fn parse_bench_args(args: &[String]) -> Option<(BenchConfig, String)> {
+    let mut config = BenchConfig::default();
+    let mut file_path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--threads" => {
+                config.thread_counts = iter
+                    .next()?
+                    .split(',')
+                    .map(|n| n.parse().ok().filter(|&n| n > 0))
+                    .collect::<Option<Vec<usize>>>()?;
+            }
+            "--repeat" => config.repeats = iter.next()?.parse().ok()?,
+            _ if file_path.is_none() => file_path = Some(arg.clone()),
+            _ => return None,
+        }
+    }
+    Some((config, file_path?))
+}
+
+// The first bytes of a file, to sniff its compression and BOM the way the
+// counters do. A file that cannot be read gives none and is left for
+// `Follower::open` to report.
+fn file_header(file_path: &str) -> Vec<u8> {
+    let mut header = Vec::new();
+    let _ = File::open(file_path).and_then(|file| file.take(4).read_to_end(&mut header));
+    header
+}
+
+fn follow(options: &Options) -> std::io::Result<()> {
+    let mut follower = Follower::open(&options.file_path)?;
+    print_totals(follower.counts());
+    let mut last = follower.counts();
+    let mut last_report = Instant::now();
+    loop {
+        thread::sleep(options.interval.unwrap_or(Duration::from_millis(1000)));
+        let event = follower.poll()?;
+        match event {
+            FollowEvent::Truncated => println!("File truncated, counting from the start"),
+            FollowEvent::Rotated => println!("File rotated, counting the new file"),
+            FollowEvent::Appended(_) | FollowEvent::Unchanged => {}
+        }
+        let counts = follower.counts();
+        if options.rate {
+            let elapsed = last_report.elapsed().as_secs_f64();
+            let (words, lines) = match event {
+                FollowEvent::Truncated | FollowEvent::Rotated => (counts.words, counts.lines),
+                _ => (counts.words - last.words, counts.lines - last.lines),
+            };
+            println!(
+                "Words/s: {:.1}, lines/s: {:.1}, total words: {}, total lines: {}",
+                words as f64 / elapsed,
+                lines as f64 / elapsed,
+                counts.words,
+                counts.lines
+            );
+            last_report = Instant::now();
+        } else if event != FollowEvent::Unchanged {
+            print_totals(counts);
+        }
+        last = counts;
+    }
+}
+
+fn print_totals(counts: Counts) {
+    println!("Total words: {}, lines: {}, bytes: {}", counts.words, counts.lines, counts.bytes);
+}
@@ -0,0 +1,195 @@
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::thread;
+
+pub const LINE_LENGTH_BUCKET: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineExtreme {
+    pub line_number: usize,
+    pub length: usize,
+}
+
+// Distribution of words and characters over the lines of a text. Lengths are
+// counted in characters; the shortest line is the shortest non-blank one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineStats {
+    pub lines: usize,
+    pub words: usize,
+    pub blank_lines: usize,
+    pub word_characters: usize,
+    pub longest_line: Option<LineExtreme>,
+    pub shortest_line: Option<LineExtreme>,
+    pub words_per_line: BTreeMap<usize, usize>,
+    pub line_lengths: BTreeMap<usize, usize>,
+}
+
+impl LineStats {
+    pub fn average_word_length(&self) -> f64 {
+        if self.words == 0 {
+            return 0.0;
+        }
+        self.word_characters as f64 / self.words as f64
+    }
+
+    pub fn average_words_per_line(&self) -> f64 {
+        if self.lines == 0 {
+            return 0.0;
+        }
+        self.words as f64 / self.lines as f64
+    }
+
+    fn add_line(&mut self, line: &str) {
+        self.lines += 1;
+        let length = line.chars().count();
+        let mut words = 0;
+        for word in line.split_whitespace() {
+            words += 1;
+            self.word_characters += word.chars().count();
+        }
+        self.words += words;
+        *self.words_per_line.entry(words).or_default() += 1;
+        *self.line_lengths.entry(length / LINE_LENGTH_BUCKET * LINE_LENGTH_BUCKET).or_default() += 1;
+
+        let current = LineExtreme { line_number: self.lines, length };
+        if words == 0 {
+            self.blank_lines += 1;
+        } else if self.shortest_line.is_none_or(|shortest| length < shortest.length) {
+            self.shortest_line = Some(current);
+        }
+        if self.longest_line.is_none_or(|longest| length > longest.length) {
+            self.longest_line = Some(current);
+        }
+    }
+
+    // Appends the statistics of the text that directly follows this one, so
+    // its line numbers are shifted by the lines counted so far.
+    fn merge(&mut self, next: LineStats) {
+        let shift = |extreme: LineExtreme| LineExtreme {
+            line_number: extreme.line_number + self.lines,
+            length: extreme.length,
+        };
+        if let Some(longest) = next.longest_line.map(shift) {
+            if self.longest_line.is_none_or(|current| longest.length > current.length) {
+                self.longest_line = Some(longest);
+            }
+        }
+        if let Some(shortest) = next.shortest_line.map(shift) {
+            if self.shortest_line.is_none_or(|current| shortest.length < current.length) {
+                self.shortest_line = Some(shortest);
+            }
+        }
+        self.lines += next.lines;
+        self.words += next.words;
+        self.blank_lines += next.blank_lines;
+        self.word_characters += next.word_characters;
+        for (words, count) in next.words_per_line {
+            *self.words_per_line.entry(words).or_default() += count;
+        }
+        for (length, count) in next.line_lengths {
+            *self.line_lengths.entry(length).or_default() += count;
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let extreme = |extreme: Option<LineExtreme>| {
+            extreme.map(|extreme| json!({ "line_number": extreme.line_number, "length": extreme.length }))
+        };
+        let value = json!({
+            "lines": self.lines,
+            "words": self.words,
+            "blank_lines": self.blank_lines,
+            "average_word_length": self.average_word_length(),
+            "average_words_per_line": self.average_words_per_line(),
+            "longest_line": extreme(self.longest_line),
+            "shortest_line": extreme(self.shortest_line),
+            "words_per_line": self.words_per_line,
+            "line_length_bucket": LINE_LENGTH_BUCKET,
+            "line_lengths": self.line_lengths,
+        });
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("Lines: {}, blank lines: {}, words: {}\n", self.lines, self.blank_lines, self.words));
+        out.push_str(&format!(
+            "Average word length: {:.2}, average words per line: {:.2}\n",
+            self.average_word_length(),
+            self.average_words_per_line()
+        ));
+        if let Some(longest) = self.longest_line {
+            out.push_str(&format!("Longest line: {} ({} chars)\n", longest.line_number, longest.length));
+        }
+        if let Some(shortest) = self.shortest_line {
+            out.push_str(&format!("Shortest line: {} ({} chars)\n", shortest.line_number, shortest.length));
+        }
+        out.push_str("\nWords per line\n");
+        let labels: Vec<(String, usize)> = self.words_per_line.iter().map(|(&k, &v)| (k.to_string(), v)).collect();
+        out.push_str(&render_histogram(&labels));
+        out.push_str("\nLine length (chars)\n");
+        let labels: Vec<(String, usize)> = self
+            .line_lengths
+            .iter()
+            .map(|(&k, &v)| (format!("{}-{}", k, k + LINE_LENGTH_BUCKET - 1), v))
+            .collect();
+        out.push_str(&render_histogram(&labels));
+        out
+    }
+}
+
+const HISTOGRAM_WIDTH: usize = 50;
+
+fn render_histogram(rows: &[(String, usize)]) -> String {
+    let max = rows.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let mut out = String::new();
+    for (label, count) in rows {
+        let bar = (count * HISTOGRAM_WIDTH).div_ceil(max);
+        out.push_str(&format!("{:>width$} | {} {}\n", label, "#".repeat(bar), count, width = label_width));
+    }
+    out
+}
+
+pub fn line_stats(text: &str) -> LineStats {
+    let num_threads: usize = thread::available_parallelism().unwrap().get();
+    let chunks = get_line_chunks(text, num_threads);
+    let partials: Vec<LineStats> = thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .iter()
+            .map(|&chunk| scope.spawn(move || line_stats_in_chunk(chunk)))
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+    let mut stats = LineStats::default();
+    for partial in partials {
+        stats.merge(partial);
+    }
+    stats
+}
+
+fn line_stats_in_chunk(chunk: &str) -> LineStats {
+    let mut stats = LineStats::default();
+    for line in chunk.lines() {
+        stats.add_line(line);
+    }
+    stats
+}
+
+// Unlike `get_chunks`, which splits on spaces, every chunk here ends right
+// after a newline so that no line is shared between two chunks.
+fn get_line_chunks(text: &str, partitions: usize) -> Vec<&str> {
+    let chunk_size = text.len() / partitions + 1;
+    let bytes = text.as_bytes();
+    let mut chunks = Vec::with_capacity(partitions);
+    let mut start = 0;
+    while start < text.len() {
+        let mut end = (start + chunk_size).min(text.len());
+        while end < text.len() && bytes[end - 1] != b'\n' {
+            end += 1;
+        }
+        chunks.push(&text[start..end]);
+        start = end;
+    }
+    chunks
+}
@@ -0,0 +1,117 @@
+use core::str;
+use std::io::{self, Read};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub words: usize,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+// Counts words, lines and bytes over a byte stream that arrives in pieces.
+// A word or a multi-byte character may be split across two `feed` calls, so
+// the counter remembers whether it stopped inside a word and keeps the bytes
+// of an incomplete UTF-8 sequence until the rest of it arrives.
+#[derive(Debug, Default)]
+pub struct StreamCounter {
+    counts: Counts,
+    in_word: bool,
+    pending: Vec<u8>,
+}
+
+impl StreamCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Continues counting after text that was already counted elsewhere,
+    // e.g. by the parallel `count_words` over a memory-mapped file.
+    pub fn resume(counts: Counts, in_word: bool) -> Self {
+        Self {
+            counts,
+            in_word,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn counts(&self) -> Counts {
+        self.counts
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.counts.bytes += bytes.len();
+        self.counts.lines += bytes.iter().filter(|&&b| b == b'\n').count();
+
+        let mut buffer = std::mem::take(&mut self.pending);
+        buffer.extend_from_slice(bytes);
+        let mut rest = &buffer[..];
+        loop {
+            match str::from_utf8(rest) {
+                Ok(text) => {
+                    self.count_text(text);
+                    break;
+                }
+                Err(err) => {
+                    let valid_up_to = err.valid_up_to();
+                    let text = unsafe { str::from_utf8_unchecked(&rest[..valid_up_to]) };
+                    self.count_text(text);
+                    match err.error_len() {
+                        // Invalid bytes are not whitespace, so they belong to a word.
+                        Some(len) => {
+                            self.enter_word();
+                            rest = &rest[valid_up_to + len..];
+                        }
+                        None => {
+                            self.pending = rest[valid_up_to..].to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Flushes an incomplete trailing character, which can only happen when the
+    // stream ends in the middle of a multi-byte sequence.
+    pub fn finish(&mut self) -> Counts {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.enter_word();
+        }
+        self.counts
+    }
+
+    fn count_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.in_word = false;
+            } else {
+                self.enter_word();
+            }
+        }
+    }
+
+    fn enter_word(&mut self) {
+        if !self.in_word {
+            self.counts.words += 1;
+            self.in_word = true;
+        }
+    }
+}
+
+pub fn count_reader<R: Read>(mut reader: R) -> io::Result<Counts> {
+    let mut counter = StreamCounter::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        counter.feed(&buffer[..read]);
+    }
+    Ok(counter.finish())
+}
@@ -1,6 +1,13 @@
 #[cfg(test)]
 mod tests {
-    use wc_command::{count_word_occurrences, count_words};
+    use std::fs::{self, OpenOptions};
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+    use wc_command::{
+        count_file, count_reader, count_word_occurrences, count_words, count_words_in_file, detect_compression,
+        line_stats, read_file_as_string, read_file_as_string_with_encoding, run_bench, BenchConfig, Compression,
+        Encoding, FollowEvent, Follower, LineExtreme, StreamCounter, Strategy,
+    };
 
     #[test]
     fn test_count_words_empty() {
@@ -70,4 +77,253 @@ mod tests {
         assert_eq!(count_word_occurrences(&text, n), 30_000_000);
     }
 
+    #[test]
+    fn test_stream_counter_word_split_across_feeds() {
+        let mut counter = StreamCounter::new();
+        counter.feed(b"hello wor");
+        counter.feed(b"ld\nfoo");
+        let counts = counter.finish();
+        assert_eq!(counts.words, 3);
+        assert_eq!(counts.lines, 1);
+        assert_eq!(counts.bytes, 15);
+    }
+
+    #[test]
+    fn test_stream_counter_multibyte_char_split_across_feeds() {
+        // "a\u{3000}b" with an ideographic space, which is whitespace, split inside its bytes
+        let text = "a\u{3000}b".as_bytes();
+        let mut counter = StreamCounter::new();
+        counter.feed(&text[..2]);
+        counter.feed(&text[2..]);
+        assert_eq!(counter.finish().words, 2);
+    }
+
+    #[test]
+    fn test_count_reader_matches_count_words() {
+        let text = "this is a test\nthis is another test\nfinally another test\n".repeat(1_000);
+        let counts = count_reader(text.as_bytes()).unwrap();
+        assert_eq!(counts.words, count_words(&text));
+        assert_eq!(counts.lines, 3_000);
+    }
+
+    #[test]
+    fn test_follower_counts_appended_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "hello world\nfoo").unwrap();
+
+        let mut follower = Follower::open(&path).unwrap();
+        assert_eq!(follower.counts().words, 3);
+        assert_eq!(follower.poll().unwrap(), FollowEvent::Unchanged);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"bar baz\n").unwrap();
+        assert_eq!(follower.poll().unwrap(), FollowEvent::Appended(8));
+        // "foo" and "bar" are one word because there is no whitespace between them
+        assert_eq!(follower.counts().words, 4);
+        assert_eq!(follower.counts().lines, 2);
+    }
+
+    #[test]
+    fn test_follower_detects_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "one two three four\n").unwrap();
+
+        let mut follower = Follower::open(&path).unwrap();
+        fs::write(&path, "five\n").unwrap();
+        assert_eq!(follower.poll().unwrap(), FollowEvent::Truncated);
+        assert_eq!(follower.counts().words, 1);
+    }
+
+    #[test]
+    fn test_follower_detects_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "one two three\n").unwrap();
+
+        let mut follower = Follower::open(&path).unwrap();
+        fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        assert_eq!(follower.poll().unwrap(), FollowEvent::Unchanged);
+
+        fs::write(&path, "four five six seven eight\n").unwrap();
+        assert_eq!(follower.poll().unwrap(), FollowEvent::Rotated);
+        assert_eq!(follower.counts().words, 5);
+    }
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_detect_compression() {
+        assert_eq!(detect_compression(&gzip("hello")), Compression::Gzip);
+        assert_eq!(detect_compression(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]), Compression::Zstd);
+        assert_eq!(detect_compression(b"hello world"), Compression::None);
+        assert_eq!(detect_compression(b""), Compression::None);
+    }
+
+    #[test]
+    fn test_count_file_gzip() {
+        let text = "this is a test\nthis is another test\n".repeat(10_000);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input.txt.gz");
+        fs::write(&path, gzip(&text)).unwrap();
+
+        let (compression, counts) = count_file(path.to_str().unwrap(), None).unwrap();
+        assert_eq!(compression, Compression::Gzip);
+        assert_eq!(counts.words, 80_000);
+        assert_eq!(counts.lines, 20_000);
+        assert_eq!(counts.bytes, text.len());
+        assert_eq!(read_file_as_string(path.to_str().unwrap()), text);
+    }
+
+    #[test]
+    fn test_count_file_zstd() {
+        let text = "hello world ".repeat(10_000);
+        let compressed = ruzstd::encoding::compress_to_vec(text.as_bytes(), ruzstd::encoding::CompressionLevel::Fastest);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input.txt.zst");
+        fs::write(&path, compressed).unwrap();
+
+        let (compression, counts) = count_file(path.to_str().unwrap(), None).unwrap();
+        assert_eq!(compression, Compression::Zstd);
+        assert_eq!(counts.words, 20_000);
+        assert_eq!(count_word_occurrences(&read_file_as_string(path.to_str().unwrap()), "world".to_string()), 10_000);
+    }
+
+    fn utf16(text: &str, bom: bool, big_endian: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let units = if bom { vec![0xfeff] } else { vec![] };
+        for unit in units.into_iter().chain(text.encode_utf16()) {
+            if big_endian {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_count_words_in_file_utf16_with_bom() {
+        let text = "Rust’s memory safety\nüber 😀 emoji\n".repeat(1_000);
+        let dir = tempfile::tempdir().unwrap();
+        let little = dir.path().join("le.txt");
+        let big = dir.path().join("be.txt");
+        fs::write(&little, utf16(&text, true, false)).unwrap();
+        fs::write(&big, utf16(&text, true, true)).unwrap();
+
+        assert_eq!(count_words_in_file(little.to_str().unwrap(), None).unwrap(), 6_000);
+        assert_eq!(count_words_in_file(big.to_str().unwrap(), None).unwrap(), 6_000);
+        assert_eq!(read_file_as_string(little.to_str().unwrap()), text);
+    }
+
+    #[test]
+    fn test_utf8_bom_is_not_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bom.txt");
+        fs::write(&path, "\u{feff} hello world\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let (_, counts) = count_file(path, None).unwrap();
+        assert_eq!(counts.words, 2);
+        assert_eq!(count_words_in_file(path, None).unwrap(), 2);
+        assert_eq!(count_words_in_file(path, Some(Encoding::Utf8)).unwrap(), 2);
+        assert_eq!(read_file_as_string(path), " hello world\n");
+    }
+
+    #[test]
+    fn test_count_file_utf16_without_bom_needs_encoding() {
+        let text = "hello world\n";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("le.txt");
+        fs::write(&path, utf16(text, false, false)).unwrap();
+
+        let (_, counts) = count_file(path.to_str().unwrap(), Some(Encoding::Utf16Le)).unwrap();
+        assert_eq!(counts.words, 2);
+        assert_eq!(counts.bytes, text.len());
+    }
+
+    #[test]
+    fn test_read_file_windows_1252_and_latin1() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cp1252.txt");
+        fs::write(&path, b"\x93caf\xe9\x94 \x80 5").unwrap();
+
+        let path = path.to_str().unwrap();
+        assert_eq!(read_file_as_string_with_encoding(path, Some(Encoding::Windows1252)).unwrap(), "“café” € 5");
+        assert_eq!(read_file_as_string_with_encoding(path, Some(Encoding::Latin1)).unwrap(), "\u{93}café\u{94} \u{80} 5");
+        assert_eq!(Encoding::from_label("CP1252"), Some(Encoding::Windows1252));
+        assert_eq!(Encoding::from_label("utf_16le"), Some(Encoding::Utf16Le));
+    }
+
+    #[test]
+    fn test_read_missing_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.txt");
+        let err = read_file_as_string_with_encoding(path.to_str().unwrap(), None).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_line_stats_small_input() {
+        let text = "hello world\n\n  \nRust is a programming language\nhi\n";
+        let stats = line_stats(text);
+        assert_eq!(stats.lines, 5);
+        assert_eq!(stats.words, 8);
+        assert_eq!(stats.blank_lines, 2);
+        assert_eq!(stats.longest_line, Some(LineExtreme { line_number: 4, length: 30 }));
+        assert_eq!(stats.shortest_line, Some(LineExtreme { line_number: 5, length: 2 }));
+        assert_eq!(stats.words_per_line.get(&0), Some(&2));
+        assert_eq!(stats.words_per_line.get(&2), Some(&1));
+        assert_eq!(stats.words_per_line.get(&5), Some(&1));
+        assert_eq!(stats.line_lengths.get(&30), Some(&1));
+        assert!((stats.average_word_length() - 38.0 / 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_line_stats_merges_chunks_with_global_line_numbers() {
+        let mut text = "a b c\nshort\n".repeat(100_000);
+        text.push_str("the longest line of them all\n");
+        text.push_str(&"x y\n".repeat(100_000));
+        let stats = line_stats(&text);
+        assert_eq!(stats.lines, 300_001);
+        assert_eq!(stats.words, 400_000 + 6 + 200_000);
+        assert_eq!(stats.longest_line, Some(LineExtreme { line_number: 200_001, length: 28 }));
+        assert_eq!(stats.shortest_line, Some(LineExtreme { line_number: 200_002, length: 3 }));
+        assert_eq!(stats.words_per_line.values().sum::<usize>(), stats.lines);
+        assert!(stats.to_json().contains("\"line_number\": 200001"));
+    }
+
+    #[test]
+    fn test_strategies_agree() {
+        let text = "Hello world\nThis is\ta test\n\n  Rust’s   safety  \u{3000}guarantees ".repeat(10_000);
+        let expected = Strategy::SingleThreaded.count_words(&text, 1);
+        assert_eq!(expected, 90_000);
+        for strategy in Strategy::ALL {
+            for threads in [1, 2, 3, 8] {
+                assert_eq!(strategy.count_words(&text, threads), expected, "{} with {} threads", strategy.name(), threads);
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_bench_reports_every_strategy_and_size() {
+        let text = "this is a test\nthis is another test\n".repeat(1_000);
+        let config = BenchConfig {
+            thread_counts: vec![1, 2],
+            sizes: vec![0.5, 1.0],
+            repeats: 1,
+        };
+        let results = run_bench(&text, &config).unwrap();
+        // the single-threaded reference runs once per size, the three parallel strategies twice
+        assert_eq!(results.len(), 2 * (1 + 3 * 2));
+        let full: Vec<_> = results.iter().filter(|result| result.bytes == text.len()).collect();
+        assert!(full.iter().all(|result| result.words == 8_000));
+        assert!(results.iter().all(|result| result.efficiency > 0.0 && result.throughput_mb_per_sec() > 0.0));
+    }
+
 }
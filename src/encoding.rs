@@ -0,0 +1,184 @@
+use std::io::{self, Read};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Windows1252,
+}
+
+impl Encoding {
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Some(Encoding::Latin1),
+            "windows-1252" | "cp1252" => Some(Encoding::Windows1252),
+            _ => None,
+        }
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => &[0xef, 0xbb, 0xbf],
+            Encoding::Utf16Le => &[0xff, 0xfe],
+            Encoding::Utf16Be => &[0xfe, 0xff],
+            Encoding::Latin1 | Encoding::Windows1252 => &[],
+        }
+    }
+}
+
+// Returns the encoding announced by a byte order mark and the length of the mark.
+pub fn detect_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be]
+        .into_iter()
+        .find(|encoding| bytes.starts_with(encoding.bom()))
+        .map(|encoding| (encoding, encoding.bom().len()))
+}
+
+// Characters for 0x80..=0x9f in Windows-1252. The five undefined bytes are
+// mapped to the C1 control with the same value, as browsers do.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+// A reader that transcodes its input to UTF-8 while it is read. Without an
+// explicit encoding the byte order mark decides, falling back to UTF-8. A
+// byte order mark is never part of the output.
+pub struct DecodeReader<R: Read> {
+    inner: R,
+    encoding: Option<Encoding>,
+    bom_checked: bool,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    output_pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> DecodeReader<R> {
+    pub fn new(inner: R, encoding: Option<Encoding>) -> Self {
+        DecodeReader {
+            inner,
+            encoding,
+            bom_checked: false,
+            input: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+            eof: false,
+        }
+    }
+
+    // The encoding in use; only known for sure after the first read.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding.unwrap_or(Encoding::Utf8)
+    }
+
+    fn fill_output(&mut self) -> io::Result<()> {
+        let mut buffer = [0u8; 64 * 1024];
+        while self.output_pos == self.output.len() && !self.eof {
+            let read = self.inner.read(&mut buffer)?;
+            if read == 0 {
+                self.eof = true;
+            }
+            self.input.extend_from_slice(&buffer[..read]);
+            if !self.bom_checked {
+                // Wait for enough bytes to tell a byte order mark apart.
+                if self.input.len() < 3 && !self.eof {
+                    continue;
+                }
+                self.strip_bom();
+            }
+            self.output.clear();
+            self.output_pos = 0;
+            self.decode_input();
+        }
+        Ok(())
+    }
+
+    fn strip_bom(&mut self) {
+        self.bom_checked = true;
+        match (self.encoding, detect_bom(&self.input)) {
+            (None, Some((encoding, len))) => {
+                self.encoding = Some(encoding);
+                self.input.drain(..len);
+            }
+            (Some(encoding), Some((bom_encoding, len))) if encoding == bom_encoding => {
+                self.input.drain(..len);
+            }
+            _ => {}
+        }
+    }
+
+    // Decodes as much of `input` as possible; bytes of a character that is
+    // not complete yet stay in `input` unless the end of the stream is reached.
+    fn decode_input(&mut self) {
+        let consumed = match self.encoding() {
+            Encoding::Utf8 => {
+                self.output.extend_from_slice(&self.input);
+                self.input.len()
+            }
+            Encoding::Latin1 => {
+                let text: String = self.input.iter().map(|&b| b as char).collect();
+                self.output.extend_from_slice(text.as_bytes());
+                self.input.len()
+            }
+            Encoding::Windows1252 => {
+                let text: String = self
+                    .input
+                    .iter()
+                    .map(|&b| match b {
+                        0x80..=0x9f => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                        _ => b as char,
+                    })
+                    .collect();
+                self.output.extend_from_slice(text.as_bytes());
+                self.input.len()
+            }
+            encoding @ (Encoding::Utf16Le | Encoding::Utf16Be) => self.decode_utf16(encoding),
+        };
+        self.input.drain(..consumed);
+    }
+
+    fn decode_utf16(&mut self, encoding: Encoding) -> usize {
+        let mut units: Vec<u16> = self
+            .input
+            .chunks_exact(2)
+            .map(|pair| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                _ => u16::from_be_bytes([pair[0], pair[1]]),
+            })
+            .collect();
+        let mut consumed = units.len() * 2;
+        if !self.eof && units.last().is_some_and(|unit| (0xd800..0xdc00).contains(unit)) {
+            // A high surrogate whose pair has not arrived yet.
+            units.pop();
+            consumed -= 2;
+        }
+        let text: String = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+        self.output.extend_from_slice(text.as_bytes());
+        if self.eof && consumed < self.input.len() {
+            self.output.extend_from_slice(char::REPLACEMENT_CHARACTER.to_string().as_bytes());
+            consumed = self.input.len();
+        }
+        consumed
+    }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill_output()?;
+        let available = &self.output[self.output_pos..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.output_pos += len;
+        Ok(len)
+    }
+}
@@ -1 +1,2 @@
+pub mod encoding;
 pub mod utils;
\ No newline at end of file
@@ -1,6 +1,9 @@
 use std::env;
-use std::fs;
-use std::io;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::encoding::{DecodeReader, Encoding};
 
 pub fn read_text_file_from_args() -> io::Result<String> {
     let args: Vec<String> = env::args().collect();
@@ -8,5 +11,13 @@ pub fn read_text_file_from_args() -> io::Result<String> {
         return Err(io::Error::new(io::ErrorKind::InvalidInput, "Usage: <file_path>"));
     }
     let file_path = &args[1];
-    fs::read_to_string(file_path)
-}
\ No newline at end of file
+    read_text_file(file_path, None)
+}
+
+// Reads a text file into a `String`, transcoding it from `encoding` or, when
+// no encoding is given, from the encoding named by its byte order mark.
+pub fn read_text_file<P: AsRef<Path>>(file_path: P, encoding: Option<Encoding>) -> io::Result<String> {
+    let mut text = String::new();
+    DecodeReader::new(File::open(file_path)?, encoding).read_to_string(&mut text)?;
+    Ok(text)
+}